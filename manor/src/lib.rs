@@ -4,21 +4,40 @@
 
 #[doc(inline)]
 pub use manor_common::{
-    collection::Collection,
+    bulk::{BulkWrite, BulkWriteResult, WriteOutcome},
+    collection::{Collection, Cursor, UpsertResult},
     error::{Error, MResult},
-    gridfs::{self, GridFS, GridFile},
+    gridfs::{self, FileLink, FileQuery, FileSort, GridFS, GridFile, HashAlgorithm},
+    hooks::Hooks,
+    index::{self, IndexSync},
+    json_schema::{self, BsonSchema},
+    integrity::{self, IntegrityReport, OnDelete, Repair},
+    query::{self, Field, Query},
+    timestamps::{self, Timestamp},
+    update::Update,
+    validate::{self, ValidationError},
+    paginate::{Page, Paginate},
+    projection::{self, Projection},
+    populate::FindMany,
+    watch::{self, ChangeEvent, ChangeStream},
     model::Model,
     types::Link,
-    client::Client
+    client::Client,
+    session::Session
 };
 
 #[doc(inline)]
-pub use manor_macros::schema;
+pub use manor_macros::{schema, BsonSchema};
 
 #[doc(hidden)]
 pub use manor_common::{
     serde,
     uuid,
     bson,
-    derive_builder
+    derive_builder,
+    mongodb,
+    inventory,
+    once_cell,
+    regex,
+    async_trait
 };
@@ -0,0 +1,670 @@
+use bson::{Bson, Document, doc, from_bson, to_bson, to_vec};
+use mongodb::options::UpdateModifications;
+
+use crate::{
+    collection::{Collection, selects_id, serialize, with_session},
+    error::{Error, MResult},
+    gridfs::FileLink,
+    integrity::link_rules,
+    model::Model,
+    query::IntoQuery,
+    soft_delete::{deletion, exclude_deleted},
+    timestamps::{Replacement, stamp_insert, stamp_replace, stamp_update},
+    update::IntoUpdate,
+    version::{bump, bump_update},
+};
+
+/// Server defaults, used if the server does not report its own limits
+const DEFAULT_MAX_BATCH_COUNT: usize = 100_000;
+const DEFAULT_MAX_BATCH_BYTES: usize = 16 * 1024 * 1024;
+
+/// Per-statement overhead of an element in a BSON array (type byte, index key & terminator)
+const ARRAY_ELEMENT_OVERHEAD: usize = 8;
+
+/// The write command a bulk operation is sent with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Command {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Command {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Insert => "insert",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+
+    fn statements(&self) -> &'static str {
+        match self {
+            Self::Insert => "documents",
+            Self::Update => "updates",
+            Self::Delete => "deletes",
+        }
+    }
+}
+
+/// A single compiled operation
+#[derive(Clone, Debug)]
+struct Operation<M: Model + Send + Sync> {
+    command: Command,
+    statement: Document,
+    inserted: Option<M::Id>,
+}
+
+/// A write error reported by the server for a single operation, or an error that prevented it from being sent
+#[derive(Clone, Debug)]
+pub struct WriteError {
+    /// The server error code (ie `11000` for duplicate keys), or `0` if the operation was rejected before being sent (ie failing validation or serialization)
+    pub code: i32,
+
+    /// The error message
+    pub message: String,
+}
+
+impl WriteError {
+    fn parse(document: &Document) -> Self {
+        Self {
+            code: document.get_i32("code").unwrap_or_default(),
+            message: document.get_str("errmsg").unwrap_or_default().to_string(),
+        }
+    }
+
+    fn rejected(error: Error) -> Self {
+        Self {
+            code: error.server_code().unwrap_or_default(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// The outcome of a single operation in a [BulkWrite]
+#[derive(Clone, Debug)]
+pub enum WriteOutcome<M: Model + Send + Sync> {
+    /// The document was inserted with this ID
+    Inserted(M::Id),
+
+    /// An update or replace matched no documents, so a new one was inserted with this ID
+    Upserted(M::Id),
+
+    /// An update, replace or delete was applied. The server only reports match/modify/delete counts per batch, so these are aggregated in [BulkWriteResult].
+    Applied,
+
+    /// The operation failed
+    Failed(WriteError),
+
+    /// The operation was not attempted, as an earlier operation in an ordered bulk write failed
+    Skipped,
+}
+
+impl<M: Model + Send + Sync> WriteOutcome<M> {
+    /// Returns `true` if the operation was applied successfully
+    pub fn succeeded(&self) -> bool {
+        !matches!(self, Self::Failed(_) | Self::Skipped)
+    }
+}
+
+/// The result of a [BulkWrite], with one [WriteOutcome] per operation (in the order they were added) and aggregated counts
+#[derive(Debug)]
+pub struct BulkWriteResult<M: Model + Send + Sync> {
+    /// Per-operation outcomes, indexed in the order operations were added
+    pub outcomes: Vec<WriteOutcome<M>>,
+
+    /// Number of inserted documents (excluding upserts)
+    pub inserted: u64,
+
+    /// Number of documents matched by updates and replacements
+    pub matched: u64,
+
+    /// Number of documents modified by updates and replacements
+    pub modified: u64,
+
+    /// Number of documents upserted by updates and replacements
+    pub upserted: u64,
+
+    /// Number of deleted documents
+    pub deleted: u64,
+
+    /// Write concern errors reported by the server, if any
+    pub write_concern_errors: Vec<WriteError>,
+}
+
+impl<M: Model + Send + Sync> BulkWriteResult<M> {
+    /// Returns the index and error of every failed operation
+    pub fn failures(&self) -> Vec<(usize, &WriteError)> {
+        self.outcomes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, outcome)| match outcome {
+                WriteOutcome::Failed(error) => Some((index, error)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns `true` if every operation was applied and no write concern errors were reported
+    pub fn is_success(&self) -> bool {
+        self.write_concern_errors.is_empty() && self.outcomes.iter().all(WriteOutcome::succeeded)
+    }
+}
+
+/// A typed builder for a batch of mixed insert, update, replace and delete operations, created with [Collection::bulk_write].
+///
+/// Operations are sent with as few write commands as possible, split to fit the server's batch count & size limits. Ordered bulk writes (the default) stop at the first failed operation,
+/// while unordered bulk writes attempt every operation. Operations that fail validation (see [Model::validate]) or serialization are never sent, and are reported as failed
+/// by [BulkWrite::execute] like operations rejected by the server.
+///
+/// Inserted and replaced documents are validated and timestamped, but bulk writes never run [crate::hooks::Hooks] (ie `before_insert` or `after_save`).
+/// Use [Collection::insert_one], [Collection::insert_many] or [Collection::save] when hooks should run.
+///
+/// Deleting documents with `#[field(delete_file)]` fields also deletes their files, once the delete command succeeds (or once the session's transaction is committed, see [crate::session::Session::delete_files]).
+///
+/// ```no_run
+/// # use manor::{schema, Collection, MResult, Update, bson::Uuid};
+/// # #[schema(collection = "users")]
+/// # pub struct User {
+/// #     #[field(id = Uuid::new)]
+/// #     pub id: Uuid,
+/// #     pub username: String,
+/// #     pub logins: i64,
+/// #     pub banned: bool,
+/// # }
+/// # async fn run(users: Collection<User>, alice: User) -> MResult<()> {
+/// let result = users
+///     .bulk_write()
+///     .insert(alice)
+///     .update_one(User::fields.username.eq("bob"), Update::new().inc(User::fields.logins, 1))
+///     .delete_many(User::fields.banned.eq(true))
+///     .ordered(false)
+///     .execute()
+///     .await?;
+/// for (index, error) in result.failures() {
+///     println!("Operation {index} failed: {}", error.message);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct BulkWrite<M: Model + Send + Sync> {
+    collection: Collection<M>,
+    operations: Vec<MResult<Operation<M>>>,
+    ordered: bool,
+}
+
+impl<M: Model + Send + Sync> BulkWrite<M> {
+    fn push(mut self, operation: MResult<Operation<M>>) -> Self {
+        self.operations.push(operation);
+        self
+    }
+
+    fn scoped_update(
+        &self,
+        query: impl IntoQuery<M>,
+        update: impl IntoUpdate<M>,
+        multi: bool,
+        upsert: bool,
+    ) -> MResult<Operation<M>> {
+        let query = self.collection.scoped(query.into_query()?);
+        Self::update_statement(query, update.into_update(), multi, upsert)
+    }
+
+    fn update_statement(
+        query: impl IntoQuery<M>,
+        update: MResult<UpdateModifications>,
+        multi: bool,
+        upsert: bool,
+    ) -> MResult<Operation<M>> {
+        Ok(Operation {
+            command: Command::Update,
+            statement: doc! {
+                "q": query.into_query()?,
+                "u": to_bson(&stamp_update::<M>(bump_update::<M>(update?))?)?,
+                "multi": multi,
+                "upsert": upsert
+            },
+            inserted: None,
+        })
+    }
+
+    /// Compiles a replacement. When `scoped`, soft-deleted documents are not matched (see [Collection::with_deleted]).
+    fn replace_statement(query: impl IntoQuery<M>, mut document: M, upsert: bool, scoped: bool) -> MResult<Operation<M>> {
+        document.validate()?;
+        let query = query.into_query()?;
+        let keep_id = !selects_id(&query, document.id());
+        let query = if scoped { exclude_deleted::<M>(query) } else { query };
+        let (query, _) = bump(query, &mut document);
+        let replacement = match stamp_replace(&mut document, keep_id)? {
+            Replacement::Document => Bson::Document(serialize(&document)?),
+            Replacement::Pipeline(pipeline) => Bson::from(pipeline),
+        };
+        Ok(Operation {
+            command: Command::Update,
+            statement: doc! {
+                "q": query,
+                "u": replacement,
+                "multi": false,
+                "upsert": upsert
+            },
+            inserted: None,
+        })
+    }
+
+    fn insert_statement(document: M) -> MResult<Operation<M>> {
+        document.validate()?;
+        let id = document.id();
+        Ok(Operation {
+            command: Command::Insert,
+            statement: serialize(&stamp_insert(document))?,
+            inserted: Some(id),
+        })
+    }
+
+    fn delete_statement(query: impl IntoQuery<M>, limit: i32) -> MResult<Operation<M>> {
+        if let Some(update) = deletion::<M>() {
+            let query = exclude_deleted::<M>(query.into_query()?);
+            return Self::update_statement(query, Ok(update.into()), limit == 0, false);
+        }
+
+        // Policies need the deleted IDs, which delete commands do not report
+        if !link_rules(&M::collection_name()).is_empty() {
+            return Err(Error::Unsupported(format!(
+                "bulk deletions from {} (targeted by on_delete policies)",
+                M::collection_name()
+            )));
+        }
+
+        Ok(Operation {
+            command: Command::Delete,
+            statement: doc! {"q": query.into_query()?, "limit": limit},
+            inserted: None,
+        })
+    }
+
+    /// Sets whether operations are applied in order, stopping at the first failure (the default), or in any order, attempting every operation
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Inserts a document
+    pub fn insert(self, document: M) -> Self {
+        self.push(Self::insert_statement(document))
+    }
+
+    /// Inserts several documents
+    pub fn insert_many(self, documents: impl IntoIterator<Item = M>) -> Self {
+        documents.into_iter().fold(self, |bulk, document| bulk.insert(document))
+    }
+
+    /// Updates the first document matching `query`. Soft-deleted documents are left as-is, unless the collection was created with [Collection::with_deleted].
+    pub fn update_one(self, query: impl IntoQuery<M>, update: impl IntoUpdate<M>) -> Self {
+        let operation = self.scoped_update(query, update, false, false);
+        self.push(operation)
+    }
+
+    /// Updates all documents matching `query`. Soft-deleted documents are left as-is, unless the collection was created with [Collection::with_deleted].
+    pub fn update_many(self, query: impl IntoQuery<M>, update: impl IntoUpdate<M>) -> Self {
+        let operation = self.scoped_update(query, update, true, false);
+        self.push(operation)
+    }
+
+    /// Updates the first document matching `query`, inserting a new document if none match. Soft-deleted documents are not matched, unless the collection was created with [Collection::with_deleted].
+    pub fn upsert_one(self, query: impl IntoQuery<M>, update: impl IntoUpdate<M>) -> Self {
+        let operation = self.scoped_update(query, update, false, true);
+        self.push(operation)
+    }
+
+    /// Replaces the first document matching `query`. For `#[field(version)]` models, only a document at the replacement's version matches (a mismatch is reported as matching nothing, or as a duplicate key failure when upserting).
+    /// Soft-deleted documents are not matched, unless the collection was created with [Collection::with_deleted].
+    pub fn replace_one(self, query: impl IntoQuery<M>, document: M) -> Self {
+        let operation = Self::replace_statement(query, document, false, !self.collection.include_deleted);
+        self.push(operation)
+    }
+
+    /// Replaces the first document matching `query`, or inserts `document` if none match. Soft-deleted documents are not matched, unless the collection was created with [Collection::with_deleted].
+    pub fn replace_or_insert_one(self, query: impl IntoQuery<M>, document: M) -> Self {
+        let operation = Self::replace_statement(query, document, true, !self.collection.include_deleted);
+        self.push(operation)
+    }
+
+    /// Saves a document (inserts or replaces by ID), like [Collection::save]
+    pub fn save(self, document: M) -> Self {
+        let query = doc! {"_id": document.id()};
+        self.push(Self::replace_statement(query, document, true, false))
+    }
+
+    /// Deletes the first document matching `query`. Documents of `#[schema(soft_delete)]` models are marked as deleted instead, and counted as modified.
+    ///
+    /// Bulk writes cannot apply `on_delete` policies, so deleting documents of a model targeted by any (see [crate::integrity::OnDelete]) is reported as failed
+    /// with an [Error::Unsupported] message.
+    pub fn delete_one(self, query: impl IntoQuery<M>) -> Self {
+        self.push(Self::delete_statement(query, 1))
+    }
+
+    /// Deletes all documents matching `query`. Documents of `#[schema(soft_delete)]` models are marked as deleted instead, and counted as modified.
+    ///
+    /// As with [BulkWrite::delete_one], this is reported as failed for models targeted by `on_delete` policies.
+    pub fn delete_many(self, query: impl IntoQuery<M>) -> Self {
+        self.push(Self::delete_statement(query, 0))
+    }
+
+    /// Returns the number of operations in this bulk write
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Returns `true` if this bulk write has no operations
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Fetches the server's batch count & size limits
+    async fn limits(&self) -> MResult<(usize, usize)> {
+        let hello = self
+            .collection
+            .client()
+            .database()
+            .run_command(doc! {"hello": 1})
+            .await?;
+        let limit = |key: &str, default: usize| match hello.get(key) {
+            Some(Bson::Int32(v)) => *v as usize,
+            Some(Bson::Int64(v)) => *v as usize,
+            _ => default,
+        };
+        Ok((
+            limit("maxWriteBatchSize", DEFAULT_MAX_BATCH_COUNT),
+            limit("maxBsonObjectSize", DEFAULT_MAX_BATCH_BYTES),
+        ))
+    }
+
+    /// Groups operation indices into batches that can each be sent as a single write command
+    fn batches(
+        operations: &[Operation<M>],
+        ordered: bool,
+        max_count: usize,
+        max_bytes: usize,
+    ) -> MResult<Vec<(Command, Vec<usize>)>> {
+        // Ordered writes can only combine consecutive operations of the same kind, while unordered writes group every operation of the same kind together
+        let mut groups: Vec<(Command, Vec<usize>)> = Vec::new();
+        for (index, operation) in operations.iter().enumerate() {
+            let existing = if ordered {
+                groups.last_mut().filter(|(command, _)| *command == operation.command)
+            } else {
+                groups.iter_mut().find(|(command, _)| *command == operation.command)
+            };
+            match existing {
+                Some((_, indices)) => indices.push(index),
+                None => groups.push((operation.command, vec![index])),
+            }
+        }
+
+        let mut batches = Vec::new();
+        for (command, indices) in groups {
+            let mut batch: Vec<usize> = Vec::new();
+            let mut bytes = 0;
+            for index in indices {
+                let size = to_vec(&operations[index].statement)?.len() + ARRAY_ELEMENT_OVERHEAD;
+                if !batch.is_empty() && (batch.len() >= max_count || bytes + size > max_bytes) {
+                    batches.push((command, std::mem::take(&mut batch)));
+                    bytes = 0;
+                }
+                batch.push(index);
+                bytes += size;
+            }
+            if !batch.is_empty() {
+                batches.push((command, batch));
+            }
+        }
+        Ok(batches)
+    }
+
+    /// Splits compiled operations into those to send (with their indices) and the initial outcome of every operation.
+    /// Operations that failed to compile are reported as failed, and ordered bulk writes send nothing from the first of them onwards.
+    fn sendable(
+        compiled: Vec<MResult<Operation<M>>>,
+        ordered: bool,
+    ) -> (Vec<usize>, Vec<Operation<M>>, Vec<WriteOutcome<M>>) {
+        let mut outcomes = vec![WriteOutcome::Skipped; compiled.len()];
+        let (mut positions, mut operations) = (Vec::new(), Vec::new());
+        for (index, operation) in compiled.into_iter().enumerate() {
+            match operation {
+                Ok(operation) => {
+                    positions.push(index);
+                    operations.push(operation);
+                }
+                Err(error) => {
+                    outcomes[index] = WriteOutcome::Failed(WriteError::rejected(error));
+                    if ordered {
+                        break;
+                    }
+                }
+            }
+        }
+        (positions, operations, outcomes)
+    }
+
+    /// Runs every operation, returning per-operation outcomes.
+    ///
+    /// Individual operation failures (including operations that failed validation or serialization) are reported in the [BulkWriteResult].
+    /// An [Err] is only returned if a command failed as a whole (ie due to a network error), in which case any earlier batches will already have been applied.
+    pub async fn execute(mut self) -> MResult<BulkWriteResult<M>> {
+        let (positions, mut operations, outcomes) = Self::sendable(std::mem::take(&mut self.operations), self.ordered);
+        let mut result = BulkWriteResult {
+            outcomes,
+            inserted: 0,
+            matched: 0,
+            modified: 0,
+            upserted: 0,
+            deleted: 0,
+            write_concern_errors: Vec::new(),
+        };
+        if operations.is_empty() {
+            return Ok(result);
+        }
+
+        let (max_count, max_bytes) = self.limits().await?;
+        let database = self.collection.client().database();
+        let count = |value: Option<&Bson>| match value {
+            Some(Bson::Int32(v)) => *v as u64,
+            Some(Bson::Int64(v)) => *v as u64,
+            _ => 0,
+        };
+
+        for (command, indices) in Self::batches(&operations, self.ordered, max_count, max_bytes)? {
+            // Files are collected right before their documents are deleted, so that earlier operations are taken into account
+            let mut files: Vec<Vec<FileLink>> = Vec::new();
+            if command == Command::Delete {
+                for index in &indices {
+                    files.push(self.collection.statement_files(&mut operations[*index].statement).await?);
+                }
+            }
+
+            let statements: Vec<Document> = indices
+                .iter()
+                .map(|i| operations[*i].statement.clone())
+                .collect();
+            let action = database.run_command(doc! {
+                command.name(): self.collection.name(),
+                command.statements(): statements,
+                "ordered": self.ordered
+            });
+            let response = with_session!(self.collection, action)?;
+
+            let errors: Vec<(usize, WriteError)> = response
+                .get_array("writeErrors")
+                .map(|errors| {
+                    errors
+                        .iter()
+                        .filter_map(Bson::as_document)
+                        .filter_map(|e| Some((e.get_i32("index").ok()? as usize, WriteError::parse(e))))
+                        .collect()
+                })
+                .unwrap_or_default();
+            if let Ok(error) = response.get_document("writeConcernError") {
+                result.write_concern_errors.push(WriteError::parse(error));
+            }
+
+            // In an ordered batch nothing after the first failure is attempted
+            let attempted = match (self.ordered, errors.iter().map(|(i, _)| *i).min()) {
+                (true, Some(first)) => first + 1,
+                _ => indices.len(),
+            };
+            for (position, index) in indices.iter().take(attempted).enumerate() {
+                result.outcomes[positions[*index]] = match errors.iter().find(|(i, _)| *i == position) {
+                    Some((_, error)) => WriteOutcome::Failed(error.clone()),
+                    None => match &operations[*index].inserted {
+                        Some(id) => WriteOutcome::Inserted(id.clone()),
+                        None => WriteOutcome::Applied,
+                    },
+                };
+            }
+
+            let n = count(response.get("n"));
+            match command {
+                Command::Insert => result.inserted += n,
+                Command::Delete => result.deleted += n,
+                Command::Update => {
+                    let upserted = response.get_array("upserted").cloned().unwrap_or_default();
+                    for upsert in upserted.iter().filter_map(Bson::as_document) {
+                        if let (Ok(position), Some(id)) = (upsert.get_i32("index"), upsert.get("_id"))
+                            && let Some(index) = indices.get(position as usize)
+                            && let Ok(id) = from_bson::<M::Id>(id.clone())
+                        {
+                            result.outcomes[positions[*index]] = WriteOutcome::Upserted(id);
+                        }
+                    }
+                    result.upserted += upserted.len() as u64;
+                    result.matched += n.saturating_sub(upserted.len() as u64);
+                    result.modified += count(response.get("nModified"));
+                }
+            }
+
+            let unlinked = indices
+                .iter()
+                .zip(files)
+                .filter(|(index, _)| result.outcomes[positions[**index]].succeeded())
+                .flat_map(|(_, files)| files)
+                .collect();
+            self.collection.delete_files(unlinked).await?;
+
+            if self.ordered && !errors.is_empty() {
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+impl<M: Model + Send + Sync> Collection<M> {
+    /// Starts a new ordered [BulkWrite] against this collection. If this collection is bound to a [crate::session::Session], the bulk write runs within it.
+    pub fn bulk_write(&self) -> BulkWrite<M> {
+        BulkWrite {
+            collection: self.clone(),
+            operations: Vec::new(),
+            ordered: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{Bson, Document, doc, to_vec};
+
+    use super::{ARRAY_ELEMENT_OVERHEAD, BulkWrite, Command, Operation, WriteOutcome};
+    use crate::testing::Record;
+
+    fn operation(command: Command, statement: Document) -> Operation<Record> {
+        Operation { command, statement, inserted: None }
+    }
+
+    fn inserts(count: i64) -> Vec<Operation<Record>> {
+        (0..count).map(|id| operation(Command::Insert, doc! {"_id": id})).collect()
+    }
+
+    fn batches(operations: &[Operation<Record>], ordered: bool, max_count: usize, max_bytes: usize) -> Vec<(Command, Vec<usize>)> {
+        BulkWrite::<Record>::batches(operations, ordered, max_count, max_bytes).unwrap()
+    }
+
+    #[test]
+    fn groups_by_command() {
+        let operations = vec![
+            operation(Command::Insert, doc! {"_id": 0}),
+            operation(Command::Insert, doc! {"_id": 1}),
+            operation(Command::Delete, doc! {"q": {}, "limit": 1}),
+            operation(Command::Insert, doc! {"_id": 2}),
+        ];
+        assert_eq!(
+            batches(&operations, true, 100, 1024),
+            vec![(Command::Insert, vec![0, 1]), (Command::Delete, vec![2]), (Command::Insert, vec![3])]
+        );
+        assert_eq!(
+            batches(&operations, false, 100, 1024),
+            vec![(Command::Insert, vec![0, 1, 3]), (Command::Delete, vec![2])]
+        );
+    }
+
+    #[test]
+    fn splits_at_count_limit() {
+        let operations = inserts(4);
+        assert_eq!(batches(&operations, true, 4, 1024), vec![(Command::Insert, vec![0, 1, 2, 3])]);
+        assert_eq!(batches(&operations, true, 2, 1024), vec![(Command::Insert, vec![0, 1]), (Command::Insert, vec![2, 3])]);
+        assert_eq!(batches(&operations, true, 3, 1024), vec![(Command::Insert, vec![0, 1, 2]), (Command::Insert, vec![3])]);
+    }
+
+    #[test]
+    fn splits_at_byte_limit() {
+        let operations = inserts(3);
+        let size = to_vec(&operations[0].statement).unwrap().len() + ARRAY_ELEMENT_OVERHEAD;
+        assert_eq!(batches(&operations, true, 100, size * 2), vec![(Command::Insert, vec![0, 1]), (Command::Insert, vec![2])]);
+        assert_eq!(
+            batches(&operations, true, 100, size * 2 - 1),
+            vec![(Command::Insert, vec![0]), (Command::Insert, vec![1]), (Command::Insert, vec![2])]
+        );
+        assert_eq!(batches(&operations, true, 100, size * 3), vec![(Command::Insert, vec![0, 1, 2])]);
+    }
+
+    #[test]
+    fn sends_oversize_operations_alone() {
+        let operations = vec![
+            operation(Command::Insert, doc! {"_id": 0}),
+            operation(Command::Insert, doc! {"_id": 1, "data": "x".repeat(64)}),
+            operation(Command::Insert, doc! {"_id": 2}),
+        ];
+        let small = to_vec(&operations[0].statement).unwrap().len() + ARRAY_ELEMENT_OVERHEAD;
+        assert_eq!(
+            batches(&operations, true, 100, small * 2),
+            vec![(Command::Insert, vec![0]), (Command::Insert, vec![1]), (Command::Insert, vec![2])]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_operations_at_their_index() {
+        let compile = || (0..4).map(|id| BulkWrite::insert_statement(Record::new(if id == 1 { -1 } else { id }))).collect::<Vec<_>>();
+
+        let (positions, operations, outcomes) = BulkWrite::<Record>::sendable(compile(), false);
+        assert_eq!(positions, vec![0, 2, 3]);
+        assert_eq!(operations.len(), 3);
+        assert!(matches!(&outcomes[1], WriteOutcome::Failed(error) if error.code == 0 && error.message.contains("id")));
+        assert!(outcomes.iter().enumerate().all(|(index, outcome)| index == 1 || matches!(outcome, WriteOutcome::Skipped)));
+
+        let (positions, _, outcomes) = BulkWrite::<Record>::sendable(compile(), true);
+        assert_eq!(positions, vec![0]);
+        assert!(matches!(&outcomes[1], WriteOutcome::Failed(_)));
+        assert!(matches!(&outcomes[2], WriteOutcome::Skipped));
+        assert!(matches!(&outcomes[3], WriteOutcome::Skipped));
+    }
+
+    #[test]
+    fn scopes_replacements_to_documents_that_are_not_deleted() {
+        let query = |scoped: bool| {
+            let operation = BulkWrite::replace_statement(doc! {"name": "a"}, Record::new(1), true, scoped).unwrap();
+            operation.statement.get_document("q").unwrap().clone()
+        };
+        assert_eq!(query(true).get("deleted_at"), Some(&Bson::Null));
+        assert_eq!(query(false).get("deleted_at"), None);
+    }
+}
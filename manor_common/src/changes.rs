@@ -0,0 +1,165 @@
+use bson::{Bson, Document, doc};
+
+use crate::{
+    collection::{Collection, serialize},
+    error::{Error, MResult},
+    model::Model,
+    version::version_filter,
+};
+
+/// Adds the `$set` & `$unset` operations turning `snapshot` into `current` to `set` & `unset`, recursing into embedded documents present in both
+fn diff_into(prefix: &str, snapshot: &Document, current: &Document, set: &mut Document, unset: &mut Document) {
+    for (key, value) in current {
+        let path = format!("{prefix}{key}");
+        match (snapshot.get(key), value) {
+            (Some(Bson::Document(old)), Bson::Document(new)) => diff_into(&format!("{path}."), old, new, set, unset),
+            (Some(old), new) if old == new => (),
+            (_, new) => {
+                set.insert(path, new.clone());
+            }
+        }
+    }
+
+    for key in snapshot.keys().filter(|k| !current.contains_key(k)) {
+        unset.insert(format!("{prefix}{key}"), "");
+    }
+}
+
+/// Computes a minimal update turning `snapshot` into `current` (ignoring `_id`), or [None] if they are identical
+pub fn diff(snapshot: &Document, current: &Document) -> Option<Document> {
+    let (mut set, mut unset) = (Document::new(), Document::new());
+    diff_into("", snapshot, current, &mut set, &mut unset);
+    set.remove("_id");
+    unset.remove("_id");
+
+    let mut update = Document::new();
+    if !set.is_empty() {
+        update.insert("$set", set);
+    }
+    if !unset.is_empty() {
+        update.insert("$unset", unset);
+    }
+    (!update.is_empty()).then_some(update)
+}
+
+/// Records the current state of `document` as its snapshot, if its model tracks changes
+pub(crate) fn record_snapshot<M: Model + Send + Sync>(document: &mut M) -> MResult<()> {
+    if M::tracks_changes() {
+        let snapshot = serialize(&*document)?;
+        document.set_snapshot(Some(snapshot));
+    }
+    Ok(())
+}
+
+impl<M: Model + Send + Sync> Collection<M> {
+    /// Writes the fields of `document` that changed since it was loaded (see [Model::save_changes]) as a single `$set`/`$unset` update, then refreshes its snapshot.
+    /// Documents without a snapshot are saved in full with [Collection::save]. Returns `false` if nothing had changed.
+    ///
+    /// [crate::hooks::Hooks::before_save] runs before changes are looked for, so that changes it makes are saved. Timestamps and the version are only updated
+    /// (and written) if something changed, and are restored if the update fails.
+    pub async fn save_changes(&self, document: &mut M) -> MResult<bool> {
+        let Some(snapshot) = document.snapshot().cloned() else {
+            self.save_in_place(document).await?;
+            return Ok(true);
+        };
+
+        document.before_save().await?;
+        if diff(&snapshot, &serialize(&*document)?).is_none() {
+            return Ok(false);
+        }
+        document.validate()?;
+
+        let unstamped = document.clone();
+        let result = self.write_changes(document, &snapshot).await;
+        if result.is_err() {
+            *document = unstamped;
+        }
+        let current = result?;
+
+        document.set_snapshot(Some(current));
+        document.after_save().await?;
+        Ok(true)
+    }
+
+    /// Stamps `document` and writes its changes since `snapshot`, returning its new serialized state
+    async fn write_changes(&self, document: &mut M, snapshot: &Document) -> MResult<Document> {
+        document.touch(false);
+        let mut query = doc! {"_id": document.id()};
+        let expected = document.version();
+        if let Some(expected) = expected {
+            query = version_filter::<M>(query, expected);
+            document.set_version(expected + 1);
+        }
+
+        let current = serialize(&*document)?;
+        if let Some(update) = diff(snapshot, &current) {
+            let result = self.update_one(query, update).await?;
+            if result.matched_count == 0 {
+                if let Some(expected) = expected
+                    && let Some(conflict) = self.conflict(doc! {"_id": document.id()}, expected).await?
+                {
+                    return Err(conflict);
+                }
+                return Err(Error::NotFound);
+            }
+        }
+        Ok(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+
+    use super::diff;
+
+    #[test]
+    fn identical_documents() {
+        let document = doc! {"_id": 1, "name": "a", "profile": {"age": 3}};
+        assert_eq!(diff(&document, &document.clone()), None);
+    }
+
+    #[test]
+    fn sets_changed_and_added_fields() {
+        assert_eq!(
+            diff(&doc! {"_id": 1, "name": "a"}, &doc! {"_id": 1, "name": "b", "age": 3}),
+            Some(doc! {"$set": {"name": "b", "age": 3}})
+        );
+    }
+
+    #[test]
+    fn unsets_removed_fields() {
+        assert_eq!(
+            diff(&doc! {"name": "a", "age": 3}, &doc! {"name": "a"}),
+            Some(doc! {"$unset": {"age": ""}})
+        );
+    }
+
+    #[test]
+    fn recurses_into_embedded_documents() {
+        assert_eq!(
+            diff(
+                &doc! {"profile": {"age": 3, "nick": "a", "links": {"site": "x"}}},
+                &doc! {"profile": {"age": 4, "links": {"site": "x", "mail": "y"}}}
+            ),
+            Some(doc! {"$set": {"profile.age": 4, "profile.links.mail": "y"}, "$unset": {"profile.nick": ""}})
+        );
+    }
+
+    #[test]
+    fn replaces_values_changing_type() {
+        assert_eq!(
+            diff(&doc! {"profile": {"age": 3}}, &doc! {"profile": null}),
+            Some(doc! {"$set": {"profile": null}})
+        );
+        assert_eq!(
+            diff(&doc! {"tags": ["a"]}, &doc! {"tags": ["a", "b"]}),
+            Some(doc! {"$set": {"tags": ["a", "b"]}})
+        );
+    }
+
+    #[test]
+    fn ignores_id() {
+        assert_eq!(diff(&doc! {"_id": 1}, &doc! {"_id": 2}), None);
+    }
+}
@@ -1,3 +1,8 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
 use once_cell::sync::OnceCell;
 
 use mongodb::options::GridFsBucketOptions;
@@ -15,8 +20,10 @@ pub(crate) static MANOR_CLIENT: OnceCell<Client> = OnceCell::new();
 /// A Manor client instance, wrapping the MongoDB client and a single database name.
 #[derive(Clone, Debug)]
 pub struct Client {
-    client: mongodb::Client,
-    database: String,
+    pub(crate) client: mongodb::Client,
+    pub(crate) database: String,
+    pub(crate) non_atomic_deletes: bool,
+    pub(crate) indexed_buckets: Arc<Mutex<HashSet<String>>>,
 }
 
 impl Client {
@@ -30,6 +37,8 @@ impl Client {
         Collection {
             collection: self.database().collection(&M::collection_name()),
             client: self.clone(),
+            session: None,
+            include_deleted: false,
         }
     }
 
@@ -37,10 +46,10 @@ impl Client {
     pub async fn connect_with_uri(uri: impl Into<String>, database: impl Into<String>) -> MResult<Self> {
         let converted = uri.into();
         let connection_str = mongodb::options::ConnectionString::parse(&converted)
-            .or_else(|e| Err(Error::InvalidUri(converted.clone(), e)))?;
+            .map_err(|e| Error::InvalidUri(converted.clone(), e))?;
         let options = mongodb::options::ClientOptions::parse(connection_str)
             .await
-            .or_else(|e| Err(Error::InvalidUri(converted.clone(), e)))?;
+            .map_err(|e| Error::InvalidUri(converted.clone(), e))?;
         Self::connect_with_options(options, database).await
     }
 
@@ -51,8 +60,10 @@ impl Client {
     ) -> MResult<Self> {
         Ok(Self {
             client: mongodb::Client::with_options(options)
-                .or_else(|e| Err(Error::ClientFailure(e)))?,
+                .map_err(Error::ClientFailure)?,
             database: database.into(),
+            non_atomic_deletes: false,
+            indexed_buckets: Arc::default(),
         })
     }
 
@@ -61,9 +72,18 @@ impl Client {
         Self {
             client,
             database: database.into(),
+            non_atomic_deletes: false,
+            indexed_buckets: Arc::default(),
         }
     }
 
+    /// Allows deletions enforcing `on_delete` link policies to run without a transaction when the server does not support them (ie standalone deployments).
+    /// Such deletions are not atomic: if a later cascade or update fails, documents deleted before it stay deleted. By default, these deletions fail with [Error::Unsupported] instead.
+    pub fn allow_non_atomic_deletes(mut self, allow: bool) -> Self {
+        self.non_atomic_deletes = allow;
+        self
+    }
+
     /// Makes this instance global. As the instance is a global [std::cell::OnceCell], this method will panic if a global client has already been set.
     pub fn as_global(self) {
         MANOR_CLIENT
@@ -110,6 +130,8 @@ impl From<mongodb::Database> for Client {
         Self {
             client: value.client().clone(),
             database: value.name().to_string(),
+            non_atomic_deletes: false,
+            indexed_buckets: Arc::default(),
         }
     }
 }
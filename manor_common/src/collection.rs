@@ -1,29 +1,91 @@
-use std::task::Poll;
+use std::{collections::HashMap, task::Poll};
 
-use bson::{doc, from_bson, to_document, Bson, Document};
+use bson::{doc, from_bson, Bson, Document};
 use futures_core::Stream;
+use futures_util::{TryStreamExt, stream::{self, BoxStream}};
+use serde::Serialize;
 use mongodb::{
     Namespace,
     options::{
         AggregateOptions, CountOptions, DeleteOptions, EstimatedDocumentCountOptions,
         FindOneAndDeleteOptions, FindOneAndReplaceOptions, FindOneAndUpdateOptions, FindOneOptions,
-        FindOptions, InsertManyOptions, InsertOneOptions, ReplaceOptions, UpdateModifications,
+        FindOptions, InsertManyOptions, InsertOneOptions, ReplaceOptions, ReturnDocument, UpdateModifications,
         UpdateOptions,
     },
     results::UpdateResult,
 };
 
 use crate::{
+    changes::record_snapshot,
     client::Client,
-    error::{Error, MResult},
+    error::{DUPLICATE_KEY, Error, MResult},
     model::Model,
+    populate::FindMany,
+    query::IntoQuery,
+    session::Session,
+    soft_delete::{deletion, exclude_deleted, find_delete_to_update_options},
+    timestamps::{Replacement, created_filter, find_and_update_options, find_replace_options, stamp_insert, stamp_replace, stamp_update, update_options},
+    update::IntoUpdate,
+    version::{bump, bump_update},
 };
 
+/// Runs a driver action, within this collection's [Session] if it is bound to one
+macro_rules! with_session {
+    ($collection:expr, $action:expr) => {
+        match &$collection.session {
+            Some(session) => $action.session(&mut *session.lock().await).await,
+            None => $action.await,
+        }
+    };
+}
+pub(crate) use with_session;
+
+/// Serializes a value to a [Document] the same way the driver does when writing it (ie not human-readable), so that manually built updates store the same BSON types
+pub(crate) fn serialize<T: Serialize>(value: &T) -> MResult<Document> {
+    Ok(bson::from_slice(&bson::to_vec(value)?)?)
+}
+
+/// Serializes a single value to [Bson] the same way the driver does (see [serialize]), so that comparisons & updates use the stored BSON types
+pub(crate) fn serialize_value<T: Serialize>(value: &T) -> Result<Bson, bson::ser::Error> {
+    #[derive(Serialize)]
+    struct Wrapper<'a, T> {
+        value: &'a T,
+    }
+
+    let bytes = bson::to_vec(&Wrapper { value })?;
+    let mut document: Document = bson::from_slice(&bytes).map_err(<bson::ser::Error as serde::ser::Error>::custom)?;
+    Ok(document.remove("value").unwrap_or(Bson::Null))
+}
+
+/// Gets a value from a document by dotted path
+pub(crate) fn lookup(document: &Document, path: &str) -> Bson {
+    let mut current = Bson::Document(document.clone());
+    for key in path.split('.') {
+        current = match current {
+            Bson::Document(mut d) => d.remove(key).unwrap_or(Bson::Null),
+            _ => return Bson::Null,
+        };
+    }
+    current
+}
+
+/// Keys an ID by its stored BSON representation (see [serialize_value]), as [Model::Id] is not required to be hashable
+pub(crate) fn id_key<M: Model + Send + Sync>(id: &M::Id) -> MResult<String> {
+    Ok(serialize_value(id)?.to_string())
+}
+
+/// Returns `true` if `query` selects exactly the document with `id`
+pub(crate) fn selects_id(query: &Document, id: impl Into<Bson>) -> bool {
+    query.len() == 1 && query.get("_id") == Some(&id.into())
+}
+
 /// A wrapper around [mongodb::Collection] with abstractions for common operations
 #[derive(Clone, Debug)]
 pub struct Collection<M: Model + Send + Sync> {
     pub(crate) collection: mongodb::Collection<M>,
     pub(crate) client: Client,
+    pub(crate) session: Option<Session>,
+    pub(crate) include_deleted: bool,
 }
 
 /// An enum describing how many operations to run, in certain cases
@@ -71,6 +133,7 @@ pub enum Find<M: Model + Send + Sync> {
 }
 
 /// The result of a Find operation
+#[allow(clippy::large_enum_variant)]
 pub enum FindResult<M: Model + Send + Sync> {
     /// A cursor over multiple documents
     Cursor(Cursor<M>),
@@ -99,6 +162,30 @@ impl<M: Model + Send + Sync> FindResult<M> {
     }
 }
 
+/// The result of an upsert operation, describing whether the document was inserted or replaced
+#[derive(Clone, Debug)]
+pub enum UpsertResult<M: Model + Send + Sync> {
+    /// No matching document existed, so a new one was inserted with this ID
+    Inserted(M::Id),
+
+    /// An existing document with this ID was replaced
+    Replaced(M::Id),
+}
+
+impl<M: Model + Send + Sync> UpsertResult<M> {
+    /// Returns the ID of the inserted or replaced document
+    pub fn id(&self) -> M::Id {
+        match self {
+            Self::Inserted(id) | Self::Replaced(id) => id.clone(),
+        }
+    }
+
+    /// Returns `true` if the document was newly inserted
+    pub fn inserted(&self) -> bool {
+        matches!(self, Self::Inserted(_))
+    }
+}
+
 #[allow(missing_docs)]
 impl<M: Model + Send + Sync> Find<M> {
     pub fn many() -> Self {
@@ -137,44 +224,32 @@ impl<M: Model + Send + Sync> Find<M> {
     }
 }
 
-/// A wrapper around [mongodb::Cursor] that attaches the current collection to results automatically
-#[pin_project::pin_project]
+/// A wrapper around [mongodb::Cursor] (or any other stream of documents) that attaches the current collection to results automatically
 pub struct Cursor<M: Model + Send + Sync> {
     pub(crate) collection: Collection<M>,
-
-    #[pin]
-    pub(crate) base: mongodb::Cursor<M>,
+    pub(crate) base: BoxStream<'static, MResult<M>>,
 }
 
 impl<M: Model + Send + Sync> Stream for Cursor<M> {
     type Item = MResult<M>;
 
     fn poll_next(
-        self: std::pin::Pin<&mut Self>,
+        mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        let projected = self.project();
-        let base = projected.base;
-        match base.poll_next(cx) {
-            Poll::Ready(Some(Ok(record))) => {
-                let mut rec = record.clone();
-                rec.attach_collection(projected.collection.clone());
-                Poll::Ready(Some(Ok(rec)))
+        match self.base.as_mut().poll_next(cx) {
+            Poll::Ready(Some(Ok(mut record))) => {
+                record.attach_collection(self.collection.clone());
+                Poll::Ready(Some(Ok(record)))
             }
-            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e.into()))),
-            Poll::Ready(None) => Poll::Ready(None),
-            Poll::Pending => Poll::Pending,
+            other => other,
         }
     }
 }
 
 impl<M: Model + Send + Sync> Collection<M> {
     fn parse_id(bson: &Bson) -> Option<M::Id> {
-        if let Ok(parsed) = from_bson::<M::Id>(bson.clone()) {
-            Some(parsed)
-        } else {
-            None
-        }
+        from_bson::<M::Id>(bson.clone()).ok()
     }
 
     /// Gets this collection's [Client]
@@ -189,10 +264,11 @@ impl<M: Model + Send + Sync> Collection<M> {
 
     /// Gets a collection from the global [Client], if present
     pub fn new_global() -> Option<Self> {
-        Client::global().and_then(|c| Some(c.collection::<M>()))
+        Client::global().map(|c| c.collection::<M>())
     }
 
     /// Gets a collection from the global [Client], panicking if the global [Client] is undefined.
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Client::global()
             .expect("Global client not initialized.")
@@ -212,13 +288,53 @@ impl<M: Model + Send + Sync> Collection<M> {
 
     /// Wraps a [mongodb::Cursor] in a [Cursor]
     pub fn cursor(&self, cursor: mongodb::Cursor<M>) -> Cursor<M> {
+        self.stream_cursor(cursor.map_err(Error::from))
+    }
+
+    /// Prepares a document loaded from this collection: attaches the collection, records its snapshot if its model tracks changes (see [Model::save_changes]) and runs [crate::hooks::Hooks::after_load]
+    pub(crate) async fn load(&self, mut record: M) -> MResult<M> {
+        record.attach_collection(self.clone());
+        record_snapshot(&mut record)?;
+        record.after_load().await?;
+        Ok(record)
+    }
+
+    /// Wraps an arbitrary stream of documents in a [Cursor], preparing each document with [Collection::load]
+    pub(crate) fn stream_cursor(&self, stream: impl Stream<Item = MResult<M>> + Send + 'static) -> Cursor<M> {
+        let collection = self.clone();
+        let loaded = stream.and_then(move |record| {
+            let collection = collection.clone();
+            async move { collection.load(record).await }
+        });
         Cursor::<M> {
             collection: self.clone(),
-            base: cursor,
+            base: Box::pin(loaded),
+        }
+    }
+
+    /// Returns a copy of this collection whose operations run within the provided [Session]. Multi-document finds on the copy buffer all results in memory (see [Session]).
+    pub fn with_session(&self, session: &Session) -> Self {
+        Self {
+            session: Some(session.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the collection of another model, using the same [Client] and bound to the same [Session] (if any)
+    pub fn sibling<T: Model + Send + Sync>(&self) -> Collection<T> {
+        let collection = self.client.collection::<T>();
+        match &self.session {
+            Some(session) => collection.with_session(session),
+            None => collection,
         }
     }
 
-    /// Runs aggregation with a defined type & options
+    /// Returns the [Session] this collection is bound to, if any
+    pub fn session(&self) -> Option<Session> {
+        self.session.clone()
+    }
+
+    /// Runs aggregation with a defined type & options. Aggregations return a raw [mongodb::Cursor], so they are not run within this collection's [Session].
     pub async fn aggregate_with_options<T>(
         &self,
         pipeline: impl IntoIterator<Item = Document>,
@@ -229,7 +345,7 @@ impl<M: Model + Send + Sync> Collection<M> {
             .with_type::<T>()
             .with_options(options)
             .await
-            .or_else(|e| Err(e.into()))
+            .map_err(Error::from)
     }
 
     /// Runs a simple untyped aggregation
@@ -252,17 +368,18 @@ impl<M: Model + Send + Sync> Collection<M> {
     /// Gets an exact document count with options
     pub async fn exact_count_with_options(
         &self,
-        query: impl Into<Document>,
+        query: impl IntoQuery<M>,
         options: impl Into<Option<CountOptions>>,
     ) -> MResult<u64> {
-        self.collection()
-            .count_documents(query.into())
-            .with_options(options)
-            .await
-            .or_else(|e| Err(e.into()))
+        let collection = self.collection();
+        let action = collection
+            .count_documents(self.scoped(query.into_query()?))
+            .with_options(options);
+        with_session!(self, action).map_err(Error::from)
     }
 
-    /// Gets an estimated document count with options
+    /// Gets an estimated document count with options. This is never run within a [Session], as the server does not support it in transactions.
+    /// Estimated counts are read from collection metadata, so they include soft-deleted documents.
     pub async fn estimated_count_with_options(
         &self,
         options: impl Into<Option<EstimatedDocumentCountOptions>>,
@@ -271,11 +388,11 @@ impl<M: Model + Send + Sync> Collection<M> {
             .estimated_document_count()
             .with_options(options)
             .await
-            .or_else(|e| Err(e.into()))
+            .map_err(Error::from)
     }
 
     /// Default exact_count
-    pub async fn exact_count(&self, query: impl Into<Document>) -> MResult<u64> {
+    pub async fn exact_count(&self, query: impl IntoQuery<M>) -> MResult<u64> {
         self.exact_count_with_options(query, None).await
     }
 
@@ -284,25 +401,24 @@ impl<M: Model + Send + Sync> Collection<M> {
         self.estimated_count_with_options(None).await
     }
 
-    /// Deletes [Ops::One] or [Ops::Many] documents with options
+    /// Deletes [Ops::One] or [Ops::Many] documents with options, enforcing any `on_delete` policies of links to this model (see [crate::integrity::OnDelete]).
+    ///
+    /// Documents of `#[schema(soft_delete)]` models are marked as deleted instead, without applying link policies (see [Collection::purge]).
     pub async fn delete_with_options(
         &self,
-        query: impl Into<Document>,
+        query: impl IntoQuery<M>,
         operations: Ops,
         options: impl Into<Option<DeleteOptions>>,
     ) -> MResult<u64> {
-        let collection = self.collection();
-        match operations {
-            Ops::Many => collection.delete_many(query.into()).with_options(options),
-            Ops::One => collection.delete_one(query.into()).with_options(options),
+        let query = query.into_query()?;
+        match deletion::<M>() {
+            Some(update) => self.soft_delete(query, update, operations, options.into()).await,
+            None => self.delete_enforced(query, operations, options.into()).await,
         }
-        .await
-        .or_else(|e| Err(e.into()))
-        .and_then(|v| Ok(v.deleted_count))
     }
 
     /// Deletes one document
-    pub async fn delete_one(&self, query: impl Into<Document>) -> MResult<()> {
+    pub async fn delete_one(&self, query: impl IntoQuery<M>) -> MResult<()> {
         match self.delete_with_options(query, Ops::One, None).await {
             Ok(1) => Ok(()),
             Ok(_) => Err(Error::NotFound),
@@ -311,135 +427,194 @@ impl<M: Model + Send + Sync> Collection<M> {
     }
 
     /// Deletes all documents matching a query
-    pub async fn delete_many(&self, query: impl Into<Document>) -> MResult<u64> {
+    pub async fn delete_many(&self, query: impl IntoQuery<M>) -> MResult<u64> {
         self.delete_with_options(query, Ops::Many, None).await
     }
 
-    /// Performs an advanced Find operation
-    pub async fn find(&self, query: impl Into<Document>, find: Find<M>) -> MResult<FindResult<M>> {
+    /// Performs an advanced Find operation. Soft-deleted documents are excluded unless this collection was created with [Collection::with_deleted],
+    /// and [Find::Delete] marks documents of `#[schema(soft_delete)]` models as deleted instead of removing them.
+    pub async fn find(&self, query: impl IntoQuery<M>, find: Find<M>) -> MResult<FindResult<M>> {
         let collection = self.collection();
-        match find {
-            Find::Many(options) => collection
-                .find(query.into())
-                .with_options(options)
-                .await
-                .and_then(|c| Ok(FindResult::Cursor(self.cursor(c))))
-                .or_else(|e| Err(e.into())),
-            Find::One(options) => collection
-                .find_one(query.into())
-                .with_options(options)
-                .await
-                .and_then(|r| Ok(FindResult::Single(r)))
-                .or_else(|e| Err(e.into())),
-            Find::Delete(options) => collection
-                .find_one_and_delete(query.into())
-                .with_options(options)
+        let (query, find) = match (find, deletion::<M>()) {
+            (Find::Delete(options), Some(update)) => (
+                exclude_deleted::<M>(query.into_query()?),
+                Find::Update {
+                    modifications: update.into(),
+                    options: find_delete_to_update_options(options),
+                },
+            ),
+            (find, _) => (self.scoped(query.into_query()?), find),
+        };
+        let result = match find {
+            Find::Many(options) => {
+                let action = collection.find(query).with_options(options);
+                if let Some(session) = &self.session {
+                    // Session cursors borrow the session for every batch, so results are buffered while the session is held
+                    let mut guard = session.lock().await;
+                    let mut cursor = action.session(&mut *guard).await?;
+                    let records: Vec<M> = cursor.stream(&mut guard).try_collect().await?;
+                    Ok(FindResult::Cursor(self.stream_cursor(stream::iter(records.into_iter().map(Ok)))))
+                } else {
+                    action
+                        .await
+                        .map(|c| FindResult::Cursor(self.cursor(c)))
+                        .map_err(Error::from)
+                }
+            }
+            Find::One(options) => {
+                let action = collection.find_one(query).with_options(options);
+                with_session!(self, action)
+                    .map(FindResult::Single)
+                    .map_err(Error::from)
+            }
+            Find::Delete(options) => self
+                .find_one_and_delete_enforced(query, options)
                 .await
-                .and_then(|r| Ok(FindResult::Single(r)))
-                .or_else(|e| Err(e.into())),
+                .map(FindResult::Single),
             Find::Replace {
-                replacement,
+                mut replacement,
                 options,
                 upsert,
-            } => collection
-                .find_one_and_replace(query.into(), replacement)
-                .with_options(options)
-                .upsert(upsert)
-                .await
-                .and_then(|r| Ok(FindResult::Single(r)))
-                .or_else(|e| Err(e.into())),
+            } => {
+                replacement.validate()?;
+                let keep_id = !selects_id(&query, replacement.id());
+                let (filter, expected) = bump(query.clone(), &mut replacement);
+                let result = match stamp_replace(&mut replacement, keep_id)? {
+                    Replacement::Document => {
+                        let action = collection
+                            .find_one_and_replace(filter, replacement)
+                            .with_options(options)
+                            .upsert(upsert);
+                        with_session!(self, action).map_err(Error::from)
+                    }
+                    Replacement::Pipeline(pipeline) => {
+                        let action = collection
+                            .find_one_and_update(filter, pipeline)
+                            .with_options(find_and_update_options(options))
+                            .upsert(upsert);
+                        with_session!(self, action).map_err(Error::from)
+                    }
+                };
+                match (result, expected) {
+                    (Ok(None), Some(expected)) => match self.conflict(query, expected).await? {
+                        Some(conflict) => Err(conflict),
+                        None => Ok(FindResult::Single(None)),
+                    },
+                    (Err(e), Some(expected)) if e.server_code() == Some(DUPLICATE_KEY) => {
+                        Err(self.conflict(query, expected).await?.unwrap_or(e))
+                    }
+                    (result, _) => result.map(FindResult::Single),
+                }
+            }
             Find::Update {
                 modifications,
                 options,
-            } => collection
-                .find_one_and_update(query.into(), modifications)
-                .with_options(options)
-                .await
-                .and_then(|r| Ok(FindResult::Single(r)))
-                .or_else(|e| Err(e.into())),
+            } => {
+                let action = collection
+                    .find_one_and_update(query, stamp_update::<M>(bump_update::<M>(modifications))?)
+                    .with_options(options);
+                with_session!(self, action)
+                    .map(FindResult::Single)
+                    .map_err(Error::from)
+            }
+        }?;
+
+        match result {
+            FindResult::Single(Some(record)) => Ok(FindResult::Single(Some(self.load(record).await?))),
+            other => Ok(other),
         }
     }
 
-    /// Finds many documents, returning an iterable [Cursor]
-    pub async fn find_many(&self, query: impl Into<Document>) -> MResult<Cursor<M>> {
-        self.find(query, Find::<M>::many())
-            .await
-            .and_then(|r| Ok(r.cursor().unwrap()))
+    /// Finds many documents. Awaiting the returned [FindMany] yields an iterable [Cursor], and [FindMany::populate] can be used to fill in linked documents.
+    pub fn find_many(&self, query: impl IntoQuery<M>) -> FindMany<M> {
+        FindMany::new(self, query)
     }
 
     /// Finds at most one document
-    pub async fn find_one(&self, query: impl Into<Document>) -> MResult<Option<M>> {
+    pub async fn find_one(&self, query: impl IntoQuery<M>) -> MResult<Option<M>> {
         self.find(query, Find::<M>::one())
             .await
-            .and_then(|r| Ok(r.single().unwrap()))
+            .map(|r| r.single().unwrap())
     }
 
-    /// Finds one document, then deletes it.
-    pub async fn find_one_and_delete(&self, query: impl Into<Document>) -> MResult<Option<M>> {
+    /// Finds one document, then deletes it, enforcing any `on_delete` policies of links to this model like [Collection::delete_one].
+    /// Documents of `#[schema(soft_delete)]` models are marked as deleted instead.
+    pub async fn find_one_and_delete(&self, query: impl IntoQuery<M>) -> MResult<Option<M>> {
         self.find(query, Find::<M>::delete())
             .await
-            .and_then(|r| Ok(r.single().unwrap()))
+            .map(|r| r.single().unwrap())
     }
 
     /// Finds one document, then replaces it
     pub async fn find_one_and_replace(
         &self,
-        query: impl Into<Document>,
+        query: impl IntoQuery<M>,
         replacement: M,
     ) -> MResult<Option<M>> {
         self.find(query, Find::<M>::replace(replacement))
             .await
-            .and_then(|r| Ok(r.single().unwrap()))
+            .map(|r| r.single().unwrap())
     }
 
     /// Finds one document, upserting if not found and replacing otherwise
     pub async fn find_one_and_upsert(
         &self,
-        query: impl Into<Document>,
+        query: impl IntoQuery<M>,
         replacement: M,
     ) -> MResult<Option<M>> {
         self.find(query, Find::<M>::replace_or_insert(replacement))
             .await
-            .and_then(|r| Ok(r.single().unwrap()))
+            .map(|r| r.single().unwrap())
     }
 
     /// Finds one document and updates it
     pub async fn find_one_and_update(
         &self,
-        query: impl Into<Document>,
-        update: impl Into<UpdateModifications>,
+        query: impl IntoQuery<M>,
+        update: impl IntoUpdate<M>,
     ) -> MResult<Option<M>> {
-        self.find(query, Find::<M>::update(update))
+        self.find(query, Find::<M>::update(update.into_update()?))
             .await
-            .and_then(|r| Ok(r.single().unwrap()))
+            .map(|r| r.single().unwrap())
     }
 
-    /// Inserts many documents with options
+    /// Inserts many documents with options, running their insert [crate::hooks::Hooks]. Fails without inserting anything if any document is invalid (see [Model::validate]).
     pub async fn insert_many_with_options(
         &self,
         documents: impl IntoIterator<Item = M>,
         options: impl Into<Option<InsertManyOptions>>,
     ) -> MResult<Vec<M::Id>> {
-        self.collection()
-            .insert_many(documents)
-            .with_options(options)
-            .await
-            .and_then(|r| Ok(r.inserted_ids.values().filter_map(Self::parse_id).collect()))
-            .or_else(|e| Err(e.into()))
+        let mut documents = documents.into_iter().collect::<Vec<M>>();
+        for document in documents.iter_mut() {
+            document.before_insert().await?;
+        }
+        documents.iter().try_for_each(M::validate)?;
+        let documents: Vec<M> = documents.into_iter().map(stamp_insert).collect();
+        let collection = self.collection();
+        let action = collection.insert_many(&documents).with_options(options);
+        let inserted = with_session!(self, action)
+            .map(|r| r.inserted_ids.values().filter_map(Self::parse_id).collect())?;
+        for document in documents.iter() {
+            document.after_insert().await?;
+        }
+        Ok(inserted)
     }
 
-    /// Inserts one document with options
+    /// Inserts one document with options, after validating it (see [Model::validate]) and running its insert [crate::hooks::Hooks]
     pub async fn insert_one_with_options(
         &self,
         document: M,
         options: impl Into<Option<InsertOneOptions>>,
     ) -> MResult<Option<M::Id>> {
-        self.collection()
-            .insert_one(document)
-            .with_options(options)
-            .await
-            .and_then(|r| Ok(Self::parse_id(&r.inserted_id)))
-            .or_else(|e| Err(e.into()))
+        let mut document = document;
+        document.before_insert().await?;
+        document.validate()?;
+        let document = stamp_insert(document);
+        let collection = self.collection();
+        let action = collection.insert_one(&document).with_options(options);
+        let inserted = with_session!(self, action).map(|r| Self::parse_id(&r.inserted_id))?;
+        document.after_insert().await?;
+        Ok(inserted)
     }
 
     /// Simplified insert_many
@@ -452,79 +627,170 @@ impl<M: Model + Send + Sync> Collection<M> {
         self.insert_one_with_options(document, None).await
     }
 
-    /// Replaces a document, with options. Optionally upserts.
+    /// Validates, versions & timestamps a replacement, then replaces a document with it. Unless `query` selects the replacement's own ID, the matched document keeps
+    /// its stored `_id` (the replacement's ID is only used when upserting). Returns whether a document was inserted or replaced, or [None] if nothing matched.
+    /// Returns [Error::Conflict] if the stored document is at a different version than the replacement's.
+    /// When `scoped`, soft-deleted documents are not matched (see [Collection::scoped]).
+    ///
+    /// By-ID replacements of documents whose `created_at` matches the stored one are plain replacements, so change streams report them as such (see [created_filter]).
+    async fn replace_stamped(
+        &self,
+        query: Document,
+        document: &mut M,
+        upsert: bool,
+        scoped: bool,
+        options: Option<ReplaceOptions>,
+    ) -> MResult<Option<UpsertResult<M>>> {
+        document.validate()?;
+        let id = document.id();
+        let by_id = selects_id(&query, id.clone());
+        let query = if scoped { self.scoped(query) } else { query };
+        let (filter, expected) = bump(query.clone(), document);
+        let collection = self.collection();
+        let outcome = |r: UpdateResult| match r.upserted_id.and_then(|i| Self::parse_id(&i)) {
+            Some(inserted) => Some(UpsertResult::Inserted(inserted)),
+            None => (r.matched_count > 0).then(|| UpsertResult::Replaced(id.clone())),
+        };
+        let result = match stamp_replace(document, !by_id)? {
+            Replacement::Document => {
+                let action = collection
+                    .replace_one(filter, &*document)
+                    .with_options(options)
+                    .upsert(upsert);
+                with_session!(self, action).map(outcome).map_err(Error::from)
+            }
+            Replacement::Pipeline(pipeline) if by_id => {
+                // A stored document with the same created_at is replaced as is, so that change streams report a replacement.
+                // Otherwise (ie for new documents), the pipeline keeps the stored created_at, or sets it when upserting.
+                let replaced = match created_filter(&filter, document)? {
+                    Some(guarded) => {
+                        let action = collection
+                            .replace_one(guarded, &*document)
+                            .with_options(options.clone())
+                            .upsert(false);
+                        with_session!(self, action).map(|r| r.matched_count > 0)
+                    }
+                    None => Ok(false),
+                };
+                match replaced {
+                    Ok(true) => Ok(Some(UpsertResult::Replaced(id.clone()))),
+                    Ok(false) => {
+                        let action = collection
+                            .update_one(filter, pipeline)
+                            .with_options(update_options(options))
+                            .upsert(upsert);
+                        with_session!(self, action).map(outcome).map_err(Error::from)
+                    }
+                    Err(e) => Err(e.into()),
+                }
+            }
+            Replacement::Pipeline(pipeline) => {
+                // The replaced document's ID is only known to the server, so it is read from the original document
+                let collection = collection.clone_with_type::<Document>();
+                let action = collection
+                    .find_one_and_update(filter, pipeline)
+                    .with_options(find_replace_options(options))
+                    .projection(doc! {"_id": 1})
+                    .return_document(ReturnDocument::Before)
+                    .upsert(upsert);
+                with_session!(self, action)
+                    .map(|original| match original {
+                        Some(original) => original.get("_id").and_then(Self::parse_id).map(UpsertResult::Replaced),
+                        // When upserting, the inserted document takes the ID from the query (if it matches one exactly), or the replacement's ID
+                        None => upsert.then(|| {
+                            UpsertResult::Inserted(query.get("_id").and_then(Self::parse_id).unwrap_or(id.clone()))
+                        }),
+                    })
+                    .map_err(Error::from)
+            }
+        };
+
+        // The document keeps its version unless it was written
+        if let Some(expected) = expected
+            && !matches!(result, Ok(Some(_)))
+        {
+            document.set_version(expected);
+        }
+
+        // A version mismatch either matches nothing, or (when upserting) attempts to insert a duplicate ID
+        match (result, expected) {
+            (Ok(None), Some(expected)) => match self.conflict(query, expected).await? {
+                Some(conflict) => Err(conflict),
+                None => Ok(None),
+            },
+            (Err(e), Some(expected)) if e.server_code() == Some(DUPLICATE_KEY) => {
+                Err(self.conflict(query, expected).await?.unwrap_or(e))
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Replaces a document, with options. Optionally upserts. Timestamp fields are maintained automatically, preserving the stored `created_at` value,
+    /// and `#[field(version)]` fields are checked & incremented (returning [Error::Conflict] if the stored document was modified in the meantime).
+    /// Unless `query` selects the document's own ID, the replaced document keeps its stored `_id`.
+    /// Soft-deleted documents are not matched, unless this collection was created with [Collection::with_deleted].
     pub async fn replace_one_with_options(
         &self,
-        query: impl Into<Document>,
-        document: M,
+        query: impl IntoQuery<M>,
+        mut document: M,
         upsert: bool,
         options: impl Into<Option<ReplaceOptions>>,
     ) -> MResult<Option<M::Id>> {
-        self.collection()
-            .replace_one(query.into(), document)
-            .with_options(options)
-            .upsert(upsert)
+        self.replace_stamped(query.into_query()?, &mut document, upsert, true, options.into())
             .await
-            .and_then(|r| Ok(r.upserted_id.and_then(|i| Self::parse_id(&i))))
-            .or_else(|e| Err(e.into()))
+            .map(|r| match r {
+                Some(UpsertResult::Inserted(id)) => Some(id),
+                _ => None,
+            })
     }
 
     /// Replaces a document without upserting
     pub async fn replace_one(
         &self,
-        query: impl Into<Document>,
+        query: impl IntoQuery<M>,
         document: M,
     ) -> MResult<Option<M::Id>> {
         self.replace_one_with_options(query, document, false, None)
             .await
     }
 
-    /// Replaces a document, or inserts it if not present
+    /// Replaces a document, or inserts it if not present. Performed as a single server-side upsert.
+    /// A replaced document keeps its stored `_id` (returned as [UpsertResult::Replaced]), even if `query` matched it by other fields.
+    /// Soft-deleted documents are not matched, unless this collection was created with [Collection::with_deleted].
     pub async fn replace_or_insert_one(
         &self,
-        query: impl Into<Document>,
-        document: M,
-    ) -> MResult<Option<M::Id>> {
-        let _query: Document = query.into();
-        if let Ok(Some(_)) = self.collection.find_one(_query.clone()).await {
-            let mut as_doc = to_document(&document).or_else(|e| Err(Error::Serialization(e)))?;
-            let _ = as_doc.remove("_id");
-            self.client().database().collection::<Document>(&M::collection_name()).replace_one(_query, as_doc).await.or_else(|e| Err(Error::MongoError(e)))?;
-            Ok(Some(document.id()))
-        } else {
-            self.insert_one(document.clone()).await?;
-            Ok(Some(document.id()))
-        }
+        query: impl IntoQuery<M>,
+        mut document: M,
+    ) -> MResult<UpsertResult<M>> {
+        self.replace_stamped(query.into_query()?, &mut document, true, true, None)
+            .await?
+            .ok_or(Error::NotFound)
     }
 
-    /// Updates [Ops::One] or [Ops::Many] documents, with options
+    /// Updates [Ops::One] or [Ops::Many] documents, with options. Timestamp fields are maintained automatically, and `#[field(version)]` fields are incremented.
+    /// Soft-deleted documents are left as-is, unless this collection was created with [Collection::with_deleted].
     pub async fn update_with_options(
         &self,
-        query: impl Into<Document>,
-        update: impl Into<UpdateModifications>,
+        query: impl IntoQuery<M>,
+        update: impl IntoUpdate<M>,
         operations: Ops,
         options: impl Into<Option<UpdateOptions>>,
     ) -> MResult<UpdateResult> {
         let collection = self.collection();
-        match operations {
-            Ops::One => collection
-                .update_one(query.into(), update)
-                .with_options(options)
-                .await
-                .or_else(|e| Err(e.into())),
-            Ops::Many => collection
-                .update_many(query.into(), update)
-                .with_options(options)
-                .await
-                .or_else(|e| Err(e.into())),
-        }
+        let query = self.scoped(query.into_query()?);
+        let update = stamp_update::<M>(bump_update::<M>(update.into_update()?))?;
+        let action = match operations {
+            Ops::One => collection.update_one(query, update).with_options(options),
+            Ops::Many => collection.update_many(query, update).with_options(options),
+        };
+        with_session!(self, action).map_err(Error::from)
     }
 
     /// Updates a single document
     pub async fn update_one(
         &self,
-        query: impl Into<Document>,
-        update: impl Into<UpdateModifications>,
+        query: impl IntoQuery<M>,
+        update: impl IntoUpdate<M>,
     ) -> MResult<UpdateResult> {
         self.update_with_options(query, update, Ops::One, None)
             .await
@@ -533,8 +799,8 @@ impl<M: Model + Send + Sync> Collection<M> {
     /// Updates many documents
     pub async fn update_many(
         &self,
-        query: impl Into<Document>,
-        update: impl Into<UpdateModifications>,
+        query: impl IntoQuery<M>,
+        update: impl IntoUpdate<M>,
     ) -> MResult<UpdateResult> {
         self.update_with_options(query, update, Ops::Many, None)
             .await
@@ -552,18 +818,71 @@ impl<M: Model + Send + Sync> Collection<M> {
 
     /// Gets a document by ID
     pub async fn get(&self, id: impl Into<M::Id>) -> MResult<Option<M>> {
-        self.find_one(doc! {"_id": Into::<M::Id>::into(id)}).await
+        self.find_one(doc! {"_id": serialize_value(&Into::<M::Id>::into(id))?}).await
     }
 
-    /// Helper function to save a document (insert or replace by ID)
-    pub async fn save(&self, document: M) -> MResult<()> {
-        self.replace_or_insert_one(doc! {"_id": document.id()}, document)
-            .await
-            .and(Ok(()))
+    /// Gets every document whose ID is in `ids`, with a single `$in` query. Missing documents are omitted, and the rest are returned in the order of `ids`.
+    pub async fn get_many<I: Into<M::Id>>(&self, ids: impl IntoIterator<Item = I>) -> MResult<Vec<M>> {
+        let ids: Vec<Bson> = ids
+            .into_iter()
+            .map(|id| serialize_value(&Into::<M::Id>::into(id)))
+            .collect::<Result<_, _>>()?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut documents: Vec<M> = self
+            .find_many(doc! {"_id": {"$in": ids.clone()}})
+            .await?
+            .try_collect()
+            .await?;
+        let mut order: HashMap<String, usize> = HashMap::new();
+        for (index, id) in ids.iter().enumerate() {
+            order.entry(id.to_string()).or_insert(index);
+        }
+        documents.sort_by_cached_key(|document| id_key::<M>(&document.id()).ok().and_then(|key| order.get(&key).copied()));
+        Ok(documents)
+    }
+
+    /// Helper function to save a document (atomically insert or replace by ID), running its save [crate::hooks::Hooks]. Returns [Error::Conflict] if a `#[field(version)]` model was modified since it was loaded.
+    pub async fn save(&self, mut document: M) -> MResult<UpsertResult<M>> {
+        self.save_in_place(&mut document).await
+    }
+
+    /// Saves a document like [Collection::save], writing back the changes made by its `before_save` hook, its timestamps and its version.
+    /// The document's snapshot is refreshed before `after_save` runs, so the hook sees the stored state.
+    pub(crate) async fn save_in_place(&self, document: &mut M) -> MResult<UpsertResult<M>> {
+        document.before_save().await?;
+        let result = self
+            .replace_stamped(doc! {"_id": document.id()}, document, true, false, None)
+            .await?
+            .ok_or(Error::NotFound)?;
+        record_snapshot(document)?;
+        document.after_save().await?;
+        Ok(result)
     }
 
-    /// Helper function to delete the passed document
+    /// Helper function to delete the passed document, running its delete [crate::hooks::Hooks]
     pub async fn delete(&self, document: M) -> MResult<()> {
-        self.delete_one(doc! {"_id": document.id()}).await
+        document.before_delete().await?;
+        self.delete_one(doc! {"_id": document.id()}).await?;
+        document.after_delete().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::Bson;
+
+    use super::{id_key, serialize};
+    use crate::testing::{Account, Record};
+
+    #[test]
+    fn keys_ids_as_stored() {
+        let account = Account { id: uuid::Uuid::new_v4() };
+        let stored = serialize(&account).unwrap().get("_id").cloned().unwrap();
+        assert_eq!(id_key::<Account>(&account.id).unwrap(), stored.to_string());
+        assert_ne!(id_key::<Account>(&account.id).unwrap(), Bson::from(account.id).to_string());
+        assert_eq!(id_key::<Record>(&3).unwrap(), Bson::Int64(3).to_string());
     }
 }
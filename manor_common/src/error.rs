@@ -1,5 +1,15 @@
+use mongodb::error::{ErrorKind, WriteFailure};
 use thiserror::Error;
 
+/// Error code returned by the server for operations unsupported by the deployment (ie transactions on a standalone server)
+pub(crate) const ILLEGAL_OPERATION: i32 = 20;
+
+/// Error code returned by the server when a collection does not exist yet
+pub(crate) const NAMESPACE_NOT_FOUND: i32 = 26;
+
+/// Error code returned by the server when a write violates a unique index
+pub(crate) const DUPLICATE_KEY: i32 = 11000;
+
 /// An enum describing possible Manor errors.
 #[derive(Error, Debug)]
 pub enum Error {
@@ -33,7 +43,81 @@ pub enum Error {
 
     /// A write operation failed
     #[error("Failed to write data to GridFS")]
-    WriteFailure(String)
+    WriteFailure(String),
+
+    /// A pagination continuation token was malformed, or was created for a different sort order
+    #[error("Invalid page token: {0}")]
+    InvalidPageToken(String),
+
+    /// A deletion was blocked by documents linking to it with an `on_delete = "restrict"` policy
+    #[error("Deletion blocked by {} referencing document(s)", .0.len())]
+    Restricted(Vec<crate::integrity::Reference>),
+
+    /// A model failed the validation rules declared with `#[field(validate(...))]`
+    #[error("Validation failed: {}", .0.iter().map(|e| e.to_string()).collect::<Vec<String>>().join(", "))]
+    Validation(Vec<crate::validate::ValidationError>),
+
+    /// A replacement of a `#[field(version)]` model was rejected, as the stored document is at a different version
+    #[error("Version conflict for document {id}: expected version {expected}, found {found:?}")]
+    Conflict {
+        /// ID of the conflicting document, as displayed by [bson::Bson]
+        id: String,
+
+        /// Version of the replacement's original document
+        expected: i64,
+
+        /// Version of the stored document ([None] if it has no version field)
+        found: Option<i64>,
+    },
+
+    /// An operation is not supported for this model (ie bulk deletions of documents targeted by `on_delete` policies)
+    #[error("Unsupported operation: {0}")]
+    Unsupported(String),
+
+    /// A builder was missing a required field
+    #[error("Missing required field: {0}")]
+    UninitializedField(String),
+}
+
+impl Error {
+    /// Returns the server error code of a MongoDB command or write error, if any (ie `11000` for duplicate keys)
+    pub fn server_code(&self) -> Option<i32> {
+        let Self::MongoError(e) = self else {
+            return None;
+        };
+        match e.kind.as_ref() {
+            ErrorKind::Command(command) => Some(command.code),
+            ErrorKind::Write(WriteFailure::WriteError(write)) => Some(write.code),
+            ErrorKind::Write(WriteFailure::WriteConcernError(write)) => Some(write.code),
+            ErrorKind::InsertMany(insert) => insert
+                .write_errors
+                .as_ref()
+                .and_then(|errors| errors.first())
+                .map(|e| e.code)
+                .or(insert.write_concern_error.as_ref().map(|e| e.code)),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if a transaction could not be started because the deployment does not support them (ie standalone servers).
+    /// The driver refuses these itself with an uncoded [ErrorKind::Transaction], while servers reply with `IllegalOperation`.
+    pub(crate) fn transactions_unsupported(&self) -> bool {
+        let Self::MongoError(e) = self else {
+            return false;
+        };
+        match e.kind.as_ref() {
+            ErrorKind::Transaction { message, .. } => message.starts_with("Transactions are not supported"),
+            _ => self.server_code() == Some(ILLEGAL_OPERATION),
+        }
+    }
+
+    /// Returns `true` if this is a MongoDB error carrying the given label (ie `TransientTransactionError`)
+    pub fn has_label(&self, label: impl AsRef<str>) -> bool {
+        match self {
+            Self::MongoError(e) | Self::ClientFailure(e) | Self::InvalidUri(_, e) => e.contains_label(label),
+            _ => false,
+        }
+    }
 }
 
 impl From<bson::de::Error> for Error {
@@ -48,6 +132,12 @@ impl From<bson::ser::Error> for Error {
     }
 }
 
+impl From<derive_builder::UninitializedFieldError> for Error {
+    fn from(value: derive_builder::UninitializedFieldError) -> Self {
+        Self::UninitializedField(value.field_name().to_string())
+    }
+}
+
 impl From<mongodb::error::Error> for Error {
     fn from(value: mongodb::error::Error) -> Self {
         Self::MongoError(value)
@@ -1,15 +1,35 @@
-use bson::{doc, from_document, to_document, Document};
+use std::{
+    collections::BTreeMap,
+    io::SeekFrom,
+    ops::{Bound, Range, RangeBounds},
+    task::{Poll, ready},
+};
+
+use bson::{doc, from_bson, from_document, to_bson, to_document, Bson, Document};
 use chrono::Utc;
-use futures_util::{AsyncRead, AsyncWrite, AsyncWriteExt};
-use mongodb::gridfs::{GridFsBucket, GridFsDownloadStream, GridFsUploadStream};
+use futures_util::{
+    AsyncRead, AsyncSeek, AsyncWrite, AsyncWriteExt, TryStreamExt,
+    future::BoxFuture,
+    stream::{self, BoxStream},
+};
+use mongodb::{
+    IndexModel,
+    gridfs::{FilesCollectionDocument, GridFsBucket},
+    options::{FindOptions, IndexOptions},
+};
+use sha2::Digest;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
+    MANOR_CLIENT,
     client::Client,
-    error::{Error, MResult},
+    error::{DUPLICATE_KEY, Error, MResult},
 };
 
+/// Size of the chunks uploaded files are split into, matching the MongoDB driver's default
+const CHUNK_SIZE: usize = 255 * 1024;
+
 /// A wrapper for MongoDB's GridFS
 #[derive(Clone, Debug)]
 pub struct GridFS {
@@ -54,32 +74,148 @@ impl GridFS {
             filename: filename.into(),
             details: None,
             fs: Some(self.clone()),
-            metadata: Some(to_document(&metadata).or_else(|e| Err::<_, Error>(e.into()))?),
+            metadata: Some(to_document(&metadata).map_err(Error::from)?),
         }
         .write()
         .await
     }
 
+    /// Creates a deduplicating [GridWriter] for the specified filename (see [GridWriter::dedup]). When committed, if the bucket already holds a file with the same
+    /// SHA-256 hash & length, the new upload is discarded and the existing file is returned instead.
+    pub async fn upload_dedup(&self, filename: impl Into<String>) -> MResult<GridWriter> {
+        Ok(self.upload(filename).await?.dedup(true))
+    }
+
+    /// Returns the bucket's files collection, holding one document per file
+    fn files(&self) -> mongodb::Collection<Document> {
+        self.client
+            .database()
+            .collection::<Document>(&format!("{}.files", self.name))
+    }
+
+    /// Returns the bucket's chunks collection, holding the content of every file
+    fn chunks(&self) -> mongodb::Collection<Document> {
+        self.client
+            .database()
+            .collection::<Document>(&format!("{}.chunks", self.name))
+    }
+
+    /// Creates the indexes used to read files & list them by name, and to look up duplicates when deduplicating uploads (see [GridWriter::dedup]).
+    /// Files committed by deduplicating writers are also indexed uniquely by hash & length, so that only one of several concurrent identical uploads is kept.
+    /// Only runs once per bucket for each [Client].
+    async fn create_indexes(&self) -> MResult<()> {
+        if self.client.indexed_buckets.lock().unwrap().contains(&self.name) {
+            return Ok(());
+        }
+
+        let chunks = IndexModel::builder()
+            .keys(doc! {"files_id": 1, "n": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        self.chunks().create_index(chunks).await?;
+        self.files()
+            .create_indexes([
+                IndexModel::builder().keys(doc! {"filename": 1, "uploadDate": 1}).build(),
+                IndexModel::builder().keys(doc! {"hashes.sha256": 1, "length": 1}).build(),
+                IndexModel::builder()
+                    .keys(doc! {"hashes.sha256": 1, "length": 1, "dedup": 1})
+                    .options(
+                        IndexOptions::builder()
+                            .unique(true)
+                            .partial_filter_expression(doc! {"dedup": true})
+                            .build(),
+                    )
+                    .build(),
+            ])
+            .await?;
+        self.client.indexed_buckets.lock().unwrap().insert(self.name.clone());
+        Ok(())
+    }
+
+    /// Converts a raw files collection document into a [GridFile] of this bucket. Returns [None] if the file's ID is not a UUID.
+    fn file(&self, mut raw: Document) -> MResult<Option<GridFile>> {
+        let hashes = match raw.remove("hashes") {
+            Some(hashes) => from_bson::<BTreeMap<String, String>>(hashes)?,
+            None => BTreeMap::new(),
+        };
+        let info = from_document::<FilesCollectionDocument>(raw)?;
+        let id = match info.id {
+            Bson::Binary(binary) => match binary.to_uuid() {
+                Ok(id) => id.into(),
+                Err(_) => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+
+        Ok(Some(GridFile {
+            id,
+            filename: info.filename.unwrap_or(id.to_string()),
+            details: Some(FileDetails {
+                length: info.length,
+                chunk_size_bytes: info.chunk_size_bytes,
+                upload_date: info.upload_date.to_chrono(),
+                hashes,
+            }),
+            metadata: info.metadata,
+            fs: Some(self.clone()),
+        }))
+    }
+
     /// Fetches an existing [GridFile] in this bucket.
     pub async fn fetch(&self, id: impl AsRef<Uuid>) -> MResult<GridFile> {
-        let info = self
-            .bucket()
+        let raw = self
+            .files()
             .find_one(doc! {"_id": id.as_ref()})
             .await
-            .or_else(|e| Err(<mongodb::error::Error as Into<Error>>::into(e)))?
+            .map_err(Error::from)?
             .ok_or(Error::NotFound)?;
+        self.file(raw)?.ok_or(Error::NotFound)
+    }
 
-        Ok(GridFile {
-            id: id.as_ref().clone(),
-            filename: info.filename.unwrap_or(id.as_ref().to_string()),
-            details: Some(FileDetails {
-                length: info.length.clone(),
-                chunk_size_bytes: info.chunk_size_bytes.clone(),
-                upload_date: info.upload_date.clone().to_chrono(),
-            }),
-            metadata: info.metadata,
-            fs: Some(self.clone()),
-        })
+    /// Finds every file matching a [FileQuery], in the query's order. Files whose IDs are not UUIDs (ie uploaded by other tools) are skipped.
+    ///
+    /// ```no_run
+    /// # use manor::{FileQuery, FileSort, GridFS, GridFile, MResult};
+    /// # use futures_util::TryStreamExt;
+    /// # async fn run(fs: GridFS, since: chrono::DateTime<chrono::Utc>) -> MResult<()> {
+    /// let images: Vec<GridFile> = fs
+    ///     .find(FileQuery::new().filename_prefix("images/").uploaded_after(since).sort_by(FileSort::UploadDate, false).page(0, 20))
+    ///     .await?
+    ///     .try_collect()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn find(&self, query: FileQuery) -> MResult<BoxStream<'static, MResult<GridFile>>> {
+        let options = FindOptions::builder()
+            .sort(query.sort())
+            .skip(query.skip)
+            .limit(query.limit)
+            .build();
+        let cursor = self
+            .files()
+            .find(query.document()?)
+            .with_options(options)
+            .await?;
+
+        let fs = self.clone();
+        Ok(Box::pin(cursor.map_err(Error::from).try_filter_map(move |raw| {
+            let file = fs.file(raw);
+            async move { file }
+        })))
+    }
+
+    /// Lists every file in this bucket, ordered by ID
+    pub async fn list(&self) -> MResult<BoxStream<'static, MResult<GridFile>>> {
+        self.find(FileQuery::new()).await
+    }
+
+    /// Counts the files matching a [FileQuery], ignoring its pagination
+    pub async fn count(&self, query: FileQuery) -> MResult<u64> {
+        self.files()
+            .count_documents(query.document()?)
+            .await
+            .map_err(Error::from)
     }
 
     /// Deletes a file by ID
@@ -87,12 +223,174 @@ impl GridFS {
         self.bucket()
             .delete(id.as_ref().into())
             .await
-            .or_else(|e| Err(e.into()))
+            .map_err(Error::from)
+    }
+}
+
+/// A field of the files collection that [FileQuery] results can be sorted by
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileSort {
+    /// Sort by filename
+    Filename,
+
+    /// Sort by upload date
+    UploadDate,
+
+    /// Sort by file length
+    Length,
+}
+
+impl FileSort {
+    fn path(&self) -> &'static str {
+        match self {
+            Self::Filename => "filename",
+            Self::UploadDate => "uploadDate",
+            Self::Length => "length",
+        }
+    }
+}
+
+/// A builder for [GridFS::find] queries, combining filters on the files collection with sorting & pagination. Every filter must match.
+#[derive(Clone, Debug, Default)]
+pub struct FileQuery {
+    conditions: Vec<Result<Document, bson::ser::Error>>,
+    sort: Vec<(FileSort, bool)>,
+    skip: Option<u64>,
+    limit: Option<i64>,
+}
+
+impl FileQuery {
+    /// Creates an empty query, matching every file
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn condition(mut self, condition: Result<Document, bson::ser::Error>) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Matches files with exactly this filename
+    pub fn filename(self, filename: impl AsRef<str>) -> Self {
+        self.condition(Ok(doc! {"filename": filename.as_ref()}))
+    }
+
+    /// Matches files whose filename starts with `prefix`
+    pub fn filename_prefix(self, prefix: impl AsRef<str>) -> Self {
+        let pattern = format!("^{}", regex::escape(prefix.as_ref()));
+        self.condition(Ok(doc! {"filename": {"$regex": pattern}}))
+    }
+
+    /// Matches files whose metadata field `key` (a dotted path within the metadata) equals `value`
+    pub fn metadata(self, key: impl AsRef<str>, value: impl Serialize) -> Self {
+        let path = format!("metadata.{}", key.as_ref());
+        self.condition(to_bson(&value).map(|value| doc! {path: value}))
+    }
+
+    /// Matches files whose metadata contains every field of `value` (serialized as a document), ie a partial metadata struct
+    pub fn metadata_matches(self, value: &impl Serialize) -> Self {
+        self.condition(to_document(value).map(|fields| {
+            fields
+                .into_iter()
+                .map(|(key, value)| (format!("metadata.{key}"), value))
+                .collect()
+        }))
+    }
+
+    /// Matches files whose content has this (hex-encoded) hash, computed with `algorithm` when the file was uploaded
+    pub fn hash(self, algorithm: HashAlgorithm, hash: impl AsRef<str>) -> Self {
+        let path = format!("hashes.{}", algorithm.name());
+        self.condition(Ok(doc! {path: hash.as_ref().to_lowercase()}))
+    }
+
+    /// Matches files uploaded at or after `date`
+    pub fn uploaded_after(self, date: chrono::DateTime<Utc>) -> Self {
+        self.condition(Ok(doc! {"uploadDate": {"$gte": bson::DateTime::from_chrono(date)}}))
+    }
+
+    /// Matches files uploaded before `date`
+    pub fn uploaded_before(self, date: chrono::DateTime<Utc>) -> Self {
+        self.condition(Ok(doc! {"uploadDate": {"$lt": bson::DateTime::from_chrono(date)}}))
+    }
+
+    /// Matches files uploaded within `range`
+    pub fn uploaded_between(self, range: Range<chrono::DateTime<Utc>>) -> Self {
+        self.uploaded_after(range.start).uploaded_before(range.end)
+    }
+
+    /// Matches files whose length (in bytes) is within `range`
+    pub fn length(self, range: impl RangeBounds<u64>) -> Self {
+        let mut bounds = Document::new();
+        match range.start_bound() {
+            Bound::Included(start) => bounds.insert("$gte", *start as i64),
+            Bound::Excluded(start) => bounds.insert("$gt", *start as i64),
+            Bound::Unbounded => None,
+        };
+        match range.end_bound() {
+            Bound::Included(end) => bounds.insert("$lte", *end as i64),
+            Bound::Excluded(end) => bounds.insert("$lt", *end as i64),
+            Bound::Unbounded => None,
+        };
+        if bounds.is_empty() {
+            return self;
+        }
+        self.condition(Ok(doc! {"length": bounds}))
+    }
+
+    /// Matches files matching a raw filter on the files collection
+    pub fn filter(self, filter: Document) -> Self {
+        self.condition(Ok(filter))
+    }
+
+    /// Orders results by `field`. Can be called several times, with earlier calls taking precedence. Ties are broken by ID.
+    pub fn sort_by(mut self, field: FileSort, ascending: bool) -> Self {
+        self.sort.push((field, ascending));
+        self
+    }
+
+    /// Skips the first `skip` results
+    pub fn skip(mut self, skip: u64) -> Self {
+        self.skip = Some(skip);
+        self
+    }
+
+    /// Returns at most `limit` results
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(i64::try_from(limit).unwrap_or(i64::MAX));
+        self
+    }
+
+    /// Selects page number `page` (starting at `0`), with `per_page` results per page
+    pub fn page(self, page: u64, per_page: u64) -> Self {
+        let per_page = per_page.max(1);
+        self.skip(page.saturating_mul(per_page)).limit(per_page)
+    }
+
+    /// Returns the compiled filter document. Only files with UUID IDs (ie uploaded through manor) are matched.
+    pub fn document(&self) -> MResult<Document> {
+        let conditions = std::iter::once(Ok(doc! {"_id": {"$type": "binData"}}))
+            .chain(self.conditions.iter().cloned())
+            .collect::<Result<Vec<Document>, _>>()?;
+        Ok(match conditions.len() {
+            1 => conditions.into_iter().next().unwrap(),
+            _ => doc! {"$and": conditions},
+        })
+    }
+
+    fn sort(&self) -> Document {
+        let mut sort: Document = self
+            .sort
+            .iter()
+            .map(|(field, ascending)| (field.path().to_string(), Bson::Int32(if *ascending { 1 } else { -1 })))
+            .collect();
+        sort.insert("_id", 1);
+        sort
     }
 }
 
 /// Metadata about a file, that is only known after the file is created.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct FileDetails {
     /// File length
     pub length: u64,
@@ -102,6 +400,10 @@ pub struct FileDetails {
 
     /// Date of upload
     pub upload_date: chrono::DateTime<Utc>,
+
+    /// Hex-encoded content hashes computed while uploading, keyed by algorithm name (see [HashAlgorithm]). Always includes `sha256` for files uploaded by a [GridWriter].
+    #[serde(default)]
+    pub hashes: BTreeMap<String, String>,
 }
 
 /// A representation of a file in GridFS
@@ -124,23 +426,46 @@ pub struct GridFile {
 }
 
 impl GridFile {
-    /// Creates a [GridReader] to read this file.
+    /// Creates a [GridReader] to read this file. The reader implements [AsyncSeek], so it can jump to any position.
     /// 
     /// <div class="warning">Panics: If the GridFS instance has not been attached.</div>
     pub async fn read(&self) -> MResult<GridReader> {
-        let reader = self
-            .fs
-            .clone()
-            .expect("Uninitialized GridFS")
-            .bucket()
-            .open_download_stream(self.id.clone().into())
-            .await
-            .or_else(|e| Err::<_, Error>(e.into()))?;
+        self.read_range(..).await
+    }
+
+    /// Creates a [GridReader] that reads the given byte range of this file (ie to serve HTTP `Range` requests). Bounds past the end of the file are clamped to its length.
+    /// The reader can still seek anywhere in the file, but never reads past the end of the range.
+    /// 
+    /// <div class="warning">Panics: If the GridFS instance has not been attached.</div>
+    pub async fn read_range(&self, range: impl RangeBounds<u64>) -> MResult<GridReader> {
+        let fs = self.fs.clone().expect("Uninitialized GridFS");
+        let details = match self.details.clone() {
+            Some(details) => details,
+            None => fs.fetch(self.id).await?.details.ok_or(Error::NotFound)?,
+        };
+
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => end.saturating_add(1),
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => details.length,
+        };
 
         Ok(GridReader {
             file: self.clone(),
-            fs: self.fs.clone().unwrap(),
-            stream: reader,
+            fs,
+            length: details.length,
+            chunk_size: details.chunk_size_bytes.max(1) as u64,
+            position: start.min(details.length),
+            end: end.min(details.length),
+            buffer: Vec::new(),
+            buffer_start: 0,
+            chunks: None,
+            next_chunk: 0,
         })
     }
 
@@ -148,38 +473,35 @@ impl GridFile {
     /// 
     /// <div class="warning">Panics: If the GridFS instance has not been attached.</div>
     pub async fn write(self) -> MResult<GridWriter> {
-        let bucket = self
-            .fs
-            .clone()
-            .expect("Uninitialized GridFS")
-            .bucket();
-        let mut stream = bucket
-            .open_upload_stream(self.filename.clone())
-            .id(self.id.clone().into());
-
-        if let Some(meta) = self.metadata.clone() {
-            stream = stream.metadata(meta);
-        }
-
-        let writer = stream.await
-            .or_else(|e| Err::<_, Error>(e.into()))?;
+        let fs = self.fs.clone().expect("Uninitialized GridFS");
+        fs.create_indexes().await?;
 
         Ok(GridWriter {
-            file: self.clone(),
-            fs: self.fs.clone().unwrap(),
-            stream: writer,
+            file: self,
+            fs,
+            buffer: Vec::new(),
+            chunks: 0,
+            length: 0,
+            pending: None,
+            hashers: vec![Hasher::new(HashAlgorithm::Sha256)],
+            dedup: false,
+            closed: false,
         })
     }
 
+    /// Returns the file's hex-encoded content hash computed with `algorithm`, if known
+    pub fn hash(&self, algorithm: HashAlgorithm) -> Option<&str> {
+        self.details
+            .as_ref()
+            .and_then(|details| details.hashes.get(algorithm.name()))
+            .map(String::as_str)
+    }
+
     /// Gets the file's metadata (if present) and attempts to convert it to the specified type. Returns [None] if no metadata exists or if deserialization fails.
     pub fn metadata<T: DeserializeOwned>(&self) -> Option<T> {
-        if let Some(meta) = self.metadata.clone() {
-            if let Ok(parsed) = from_document::<T>(meta) {
-                return Some(parsed);
-            }
-        }
-
-        None
+        self.metadata
+            .clone()
+            .and_then(|meta| from_document::<T>(meta).ok())
     }
 
     /// Returns the raw [bson::Document] of the metadata, if present.
@@ -188,24 +510,238 @@ impl GridFile {
     }
 }
 
-/// A wrapper around [mongodb::gridfs::GridFsUploadStream]
-#[pin_project::pin_project]
+/// A reference to a [GridFile] stored in a model field, serialized as the file's bucket name & ID. The file is fetched lazily with [FileLink::resolve].
+///
+/// Fields marked `#[field(delete_file)]` delete their linked files when the owning document is deleted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FileLink {
+    /// Name of the [GridFS] bucket holding the file
+    pub bucket: String,
+
+    /// The ID of the linked file
+    #[serde(with = "bson::serde_helpers::uuid_1_as_binary")]
+    pub id: Uuid,
+
+    #[serde(skip)]
+    resolved: Option<GridFile>,
+
+    #[serde(skip)]
+    client: Option<Client>,
+}
+
+impl FileLink {
+    /// Creates a link to a file in the given bucket
+    pub fn new(bucket: impl Into<String>, id: Uuid) -> Self {
+        Self {
+            bucket: bucket.into(),
+            id,
+            resolved: None,
+            client: None,
+        }
+    }
+
+    /// Gets either the local or global client (in that order of precedence). Panics if no client has been initialized.
+    pub fn client(&self) -> Client {
+        self.client.clone().unwrap_or(
+            MANOR_CLIENT
+                .get()
+                .expect("This FileLink has no connection to a client.")
+                .clone(),
+        )
+    }
+
+    /// Attaches a [Client] to this [FileLink]
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Returns the [GridFS] bucket holding the file
+    pub fn fs(&self) -> GridFS {
+        self.client().named_grid_fs(self.bucket.clone())
+    }
+
+    /// Fetches the linked file and returns it. If the file has already been fetched, just returns it directly.
+    pub async fn resolve(&mut self) -> MResult<GridFile> {
+        match self.resolved.clone() {
+            Some(file) => Ok(file),
+            None => self.refresh().await,
+        }
+    }
+
+    /// Forces the linked file to be fetched again, and returns it
+    pub async fn refresh(&mut self) -> MResult<GridFile> {
+        let file = self.fs().fetch(self.id).await?;
+        self.resolved = Some(file.clone());
+        Ok(file)
+    }
+
+    /// Gets a reference to the linked file, if resolved
+    pub fn value(&self) -> Option<&GridFile> {
+        self.resolved.as_ref()
+    }
+
+    /// Deletes the linked file from its bucket
+    pub async fn delete(&self) -> MResult<()> {
+        self.fs().delete(self.id).await
+    }
+}
+
+impl From<GridFile> for FileLink {
+    fn from(value: GridFile) -> Self {
+        Self {
+            bucket: value.fs.as_ref().map(|fs| fs.name()).unwrap_or(String::from("default")),
+            id: value.id,
+            client: value.fs.as_ref().map(|fs| fs.client()),
+            resolved: Some(value),
+        }
+    }
+}
+
+/// A hash algorithm that [GridWriter] can compute while uploading. SHA-256 is always computed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// MD5
+    Md5,
+
+    /// SHA-1
+    Sha1,
+
+    /// SHA-256
+    Sha256,
+
+    /// SHA-512
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// Returns the name this algorithm's hashes are stored under, in [FileDetails::hashes]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Md5 => "md5",
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
+            Self::Sha512 => "sha512",
+        }
+    }
+}
+
+/// An in-progress hash of an upload
+#[derive(Clone)]
+enum Hasher {
+    Md5(md5::Md5),
+    Sha1(sha1::Sha1),
+    Sha256(sha2::Sha256),
+    Sha512(sha2::Sha512),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Md5 => Self::Md5(md5::Md5::new()),
+            HashAlgorithm::Sha1 => Self::Sha1(sha1::Sha1::new()),
+            HashAlgorithm::Sha256 => Self::Sha256(sha2::Sha256::new()),
+            HashAlgorithm::Sha512 => Self::Sha512(sha2::Sha512::new()),
+        }
+    }
+
+    fn algorithm(&self) -> HashAlgorithm {
+        match self {
+            Self::Md5(_) => HashAlgorithm::Md5,
+            Self::Sha1(_) => HashAlgorithm::Sha1,
+            Self::Sha256(_) => HashAlgorithm::Sha256,
+            Self::Sha512(_) => HashAlgorithm::Sha512,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Md5(hasher) => hasher.update(data),
+            Self::Sha1(hasher) => hasher.update(data),
+            Self::Sha256(hasher) => hasher.update(data),
+            Self::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Self::Md5(hasher) => hex::encode(hasher.finalize()),
+            Self::Sha1(hasher) => hex::encode(hasher.finalize()),
+            Self::Sha256(hasher) => hex::encode(hasher.finalize()),
+            Self::Sha512(hasher) => hex::encode(hasher.finalize()),
+        }
+    }
+}
+
+/// A writer uploading a file to a [GridFS] bucket, hashing its content as it is written.
+///
+/// Chunks are written to the bucket's `<bucket>.chunks` collection as they fill up, and the file's document (including its hashes) is only written by [GridWriter::commit],
+/// so the file does not exist until it is committed. Uploads that should not be kept must be discarded with [GridWriter::abort], which deletes the chunks written so far
+/// (failed commits do so themselves). Writers dropped without committing or aborting leave their chunks behind, and log a warning with [tracing].
 pub struct GridWriter {
     pub(crate) file: GridFile,
     pub(crate) fs: GridFS,
+    buffer: Vec<u8>,
+    chunks: u32,
+    length: u64,
+    pending: Option<BoxFuture<'static, MResult<()>>>,
+    hashers: Vec<Hasher>,
+    dedup: bool,
+    closed: bool,
+}
 
-    #[pin]
-    pub(crate) stream: GridFsUploadStream,
+impl Drop for GridWriter {
+    fn drop(&mut self) {
+        if !self.closed && self.chunks > 0 {
+            tracing::warn!(file = %self.file.id, bucket = %self.fs.name, "GridWriter dropped without being committed or aborted, leaving its chunks behind");
+        }
+    }
 }
 
-/// A wrapper around [mongodb::gridfs::GridFsDownloadStream]
-#[pin_project::pin_project]
+/// A seekable reader over a [GridFile], reading chunks directly from the bucket's `<bucket>.chunks` collection
 pub struct GridReader {
     pub(crate) file: GridFile,
     pub(crate) fs: GridFS,
+    length: u64,
+    chunk_size: u64,
+    position: u64,
+    end: u64,
+    buffer: Vec<u8>,
+    buffer_start: u64,
+    chunks: Option<BoxStream<'static, MResult<Document>>>,
+    next_chunk: u64,
+}
+
+impl GridReader {
+    /// Returns the file being read
+    pub fn file(&self) -> &GridFile {
+        &self.file
+    }
+
+    /// Returns the current position within the file
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Opens a stream over the file's chunks, starting at chunk `n`
+    fn open_chunks(&self, n: u64) -> BoxStream<'static, MResult<Document>> {
+        let chunks = self
+            .fs
+            .client
+            .database()
+            .collection::<Document>(&format!("{}.chunks", self.fs.name));
+        let filter = doc! {"files_id": Bson::from(self.file.id), "n": {"$gte": n as i64}};
+        Box::pin(
+            stream::once(async move { chunks.find(filter).sort(doc! {"n": 1}).await })
+                .try_flatten()
+                .map_err(Error::from),
+        )
+    }
+}
 
-    #[pin]
-    pub(crate) stream: GridFsDownloadStream,
+/// Converts a Manor error into an IO error, for [GridReader]'s IO traits
+fn io_error(error: Error) -> std::io::Error {
+    std::io::Error::other(error)
 }
 
 impl AsyncRead for GridReader {
@@ -214,8 +750,98 @@ impl AsyncRead for GridReader {
         cx: &mut std::task::Context<'_>,
         buf: &mut [u8],
     ) -> std::task::Poll<std::io::Result<usize>> {
-        let this = self.project();
-        this.stream.poll_read(cx, buf)
+        let this = self.get_mut();
+        loop {
+            if this.position >= this.end || buf.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+
+            let buffer_end = this.buffer_start + this.buffer.len() as u64;
+            if (this.buffer_start..buffer_end).contains(&this.position) {
+                let offset = (this.position - this.buffer_start) as usize;
+                let count = buf
+                    .len()
+                    .min((buffer_end - this.position) as usize)
+                    .min((this.end - this.position) as usize);
+                buf[..count].copy_from_slice(&this.buffer[offset..offset + count]);
+                this.position += count as u64;
+                return Poll::Ready(Ok(count));
+            }
+
+            // Chunks are streamed in order, so the cursor is only reopened after seeking
+            let wanted = this.position / this.chunk_size;
+            if this.chunks.is_none() || this.next_chunk != wanted {
+                this.chunks = Some(this.open_chunks(wanted));
+                this.next_chunk = wanted;
+            }
+
+            let chunk = match this.chunks.as_mut().unwrap().as_mut().poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Ok(chunk))) => chunk,
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(io_error(e))),
+                Poll::Ready(None) => {
+                    return Poll::Ready(Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        format!("Missing chunk {wanted} of file {}", this.file.id),
+                    )));
+                }
+            };
+
+            let n = match chunk.get("n") {
+                Some(Bson::Int32(n)) => *n as u64,
+                Some(Bson::Int64(n)) => *n as u64,
+                _ => u64::MAX,
+            };
+            let Ok(data) = chunk.get_binary_generic("data") else {
+                return Poll::Ready(Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Malformed chunk {wanted} of file {}", this.file.id),
+                )));
+            };
+            if n != wanted {
+                return Poll::Ready(Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!("Missing chunk {wanted} of file {}", this.file.id),
+                )));
+            }
+
+            this.buffer = data.clone();
+            this.buffer_start = n * this.chunk_size;
+            this.next_chunk = n + 1;
+
+            // A chunk shorter than expected would otherwise be reloaded forever
+            if this.position >= this.buffer_start + this.buffer.len() as u64 {
+                return Poll::Ready(Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Truncated chunk {wanted} of file {}", this.file.id),
+                )));
+            }
+        }
+    }
+}
+
+impl AsyncSeek for GridReader {
+    fn poll_seek(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+        pos: SeekFrom,
+    ) -> std::task::Poll<std::io::Result<u64>> {
+        let this = self.get_mut();
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => this.length.checked_add_signed(offset),
+            SeekFrom::Current(offset) => this.position.checked_add_signed(offset),
+        };
+        match target {
+            Some(target) => {
+                this.position = target;
+                Poll::Ready(Ok(target))
+            }
+            None => Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            ))),
+        }
     }
 }
 
@@ -225,46 +851,240 @@ impl AsyncWrite for GridWriter {
         cx: &mut std::task::Context<'_>,
         buf: &[u8],
     ) -> std::task::Poll<std::io::Result<usize>> {
-        let this = self.project();
-        this.stream.poll_write(cx, buf)
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+
+        let written = buf.len().min(CHUNK_SIZE - this.buffer.len());
+        this.buffer.extend_from_slice(&buf[..written]);
+        for hasher in this.hashers.iter_mut() {
+            hasher.update(&buf[..written]);
+        }
+        this.length += written as u64;
+        if this.buffer.len() >= CHUNK_SIZE {
+            this.write_chunk();
+        }
+        Poll::Ready(Ok(written))
     }
 
     fn poll_flush(
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<std::io::Result<()>> {
-        let this = self.project();
-        this.stream.poll_flush(cx)
+        self.get_mut().poll_pending(cx)
     }
 
     fn poll_close(
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<std::io::Result<()>> {
-        let this = self.project();
-        this.stream.poll_close(cx)
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        if !this.buffer.is_empty() {
+            this.write_chunk();
+        }
+        this.poll_pending(cx)
     }
 }
 
 impl GridWriter {
-    /// Closes the writer, saves the file to the database, and retrieves the resulting [GridFile]
+    /// Starts writing the buffered data as the next chunk
+    fn write_chunk(&mut self) {
+        let chunk = doc! {
+            "files_id": Bson::from(self.file.id),
+            "n": self.chunks as i32,
+            "data": bson::Binary {
+                subtype: bson::spec::BinarySubtype::Generic,
+                bytes: std::mem::take(&mut self.buffer),
+            }
+        };
+        self.chunks += 1;
+        let chunks = self.fs.chunks();
+        self.pending = Some(Box::pin(async move {
+            chunks.insert_one(chunk).await?;
+            Ok(())
+        }));
+    }
+
+    /// Waits for the chunk being written, if any
+    fn poll_pending(&mut self, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+        if let Some(pending) = self.pending.as_mut() {
+            let result = ready!(pending.as_mut().poll(cx));
+            self.pending = None;
+            result.map_err(io_error)?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Additionally computes a hash with `algorithm` while uploading, stored in [FileDetails::hashes] on commit
+    pub fn hash(mut self, algorithm: HashAlgorithm) -> Self {
+        if !self.hashers.iter().any(|h| h.algorithm() == algorithm) {
+            self.hashers.push(Hasher::new(algorithm));
+        }
+        self
+    }
+
+    /// Sets whether [GridWriter::commit] returns an existing file with the same SHA-256 hash & length (discarding this upload) instead of keeping a duplicate.
+    ///
+    /// Deduplicating uploads never keep duplicates of each other, even when committed concurrently: their files are indexed uniquely by hash & length,
+    /// and the upload that loses the race returns the winner's file. Other uploads are never deduplicated, so identical files uploaded without
+    /// deduplication may still exist alongside a deduplicated one.
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Discards the upload, deleting the chunks written so far
+    pub async fn abort(mut self) -> MResult<()> {
+        self.discard().await
+    }
+
+    /// Deletes the chunks written so far, once the chunk being written (if any) is stored, so that it cannot land after the deletion
+    async fn discard(&mut self) -> MResult<()> {
+        if let Some(pending) = self.pending.take() {
+            let _ = pending.await;
+        }
+        self.fs
+            .chunks()
+            .delete_many(doc! {"files_id": Bson::from(self.file.id)})
+            .await?;
+        self.closed = true;
+        Ok(())
+    }
+
+    /// Finds a committed file with the same SHA-256 hash & length as this upload
+    async fn duplicate(&self, hashes: &BTreeMap<String, String>) -> MResult<Option<GridFile>> {
+        let existing = self
+            .fs
+            .files()
+            .find_one(doc! {
+                "hashes.sha256": hashes.get(HashAlgorithm::Sha256.name()),
+                "length": self.length as i64
+            })
+            .await?;
+        Ok(existing.map(|raw| self.fs.file(raw)).transpose()?.flatten())
+    }
+
+    /// Closes the writer, saves the file (and its hashes) to the database, and retrieves the resulting [GridFile].
+    /// If deduplicating (see [GridWriter::dedup]) and an identical file already exists, the upload is deleted and the existing file is returned instead.
     pub async fn commit(mut self) -> MResult<GridFile> {
+        match self.store().await {
+            Err(error) if !self.closed => {
+                // Nothing was committed, so the upload is discarded
+                let _ = self.discard().await;
+                Err(error)
+            }
+            result => result,
+        }
+    }
+
+    /// Closes the writer and stores the file's document (see [GridWriter::commit])
+    async fn store(&mut self) -> MResult<GridFile> {
         self.close()
             .await
-            .or_else(|e| Err(Error::WriteFailure(e.to_string())))?;
-        let info = self
-            .fs
-            .bucket()
-            .find_one(doc! {"_id": self.file.id})
-            .await
-            .or_else(|e| Err(<mongodb::error::Error as Into<Error>>::into(e)))?
-            .ok_or(Error::NotFound)?;
-        let mut created = self.file.clone();
-        created.details = Some(FileDetails {
-            length: info.length,
-            chunk_size_bytes: info.chunk_size_bytes,
-            upload_date: info.upload_date.to_chrono(),
-        });
-        Ok(created)
+            .map_err(|e| Error::WriteFailure(e.to_string()))?;
+        let hashes: BTreeMap<String, String> = std::mem::take(&mut self.hashers)
+            .into_iter()
+            .map(|hasher| (hasher.algorithm().name().to_string(), hasher.finalize()))
+            .collect();
+        let files = self.fs.files();
+        let id = Bson::from(self.file.id);
+
+        if self.dedup
+            && let Some(existing) = self.duplicate(&hashes).await?
+        {
+            self.discard().await?;
+            return Ok(existing);
+        }
+
+        // The hashes are part of the file's document, so a file is never stored without them
+        let mut document = doc! {
+            "_id": id,
+            "length": self.length as i64,
+            "chunkSize": CHUNK_SIZE as i32,
+            "uploadDate": bson::DateTime::now(),
+            "filename": self.file.filename.clone(),
+            "hashes": to_bson(&hashes)?
+        };
+        if let Some(metadata) = self.file.metadata.clone() {
+            document.insert("metadata", metadata);
+        }
+        if self.dedup {
+            document.insert("dedup", true);
+        }
+
+        match files.insert_one(document).await.map_err(Error::from) {
+            Ok(_) => self.closed = true,
+            // A concurrent deduplicating upload of the same content was committed first
+            Err(e) if self.dedup && e.server_code() == Some(DUPLICATE_KEY) => {
+                let existing = self.duplicate(&hashes).await?.ok_or(e)?;
+                self.discard().await?;
+                return Ok(existing);
+            }
+            Err(e) => return Err(e),
+        }
+        self.fs.fetch(self.file.id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+
+    use super::{FileQuery, FileSort};
+
+    #[test]
+    fn empty_query_matches_uuid_files() {
+        assert_eq!(FileQuery::new().document().unwrap(), doc! {"_id": {"$type": "binData"}});
+    }
+
+    #[test]
+    fn combines_conditions() {
+        let query = FileQuery::new().filename("a.txt").length(1..=10);
+        assert_eq!(
+            query.document().unwrap(),
+            doc! {"$and": [
+                {"_id": {"$type": "binData"}},
+                {"filename": "a.txt"},
+                {"length": {"$gte": 1_i64, "$lte": 10_i64}}
+            ]}
+        );
+    }
+
+    #[test]
+    fn escapes_filename_prefixes() {
+        let query = FileQuery::new().filename_prefix("a.b");
+        assert_eq!(
+            query.document().unwrap(),
+            doc! {"$and": [{"_id": {"$type": "binData"}}, {"filename": {"$regex": "^a\\.b"}}]}
+        );
+    }
+
+    #[test]
+    fn ignores_unbounded_lengths() {
+        assert_eq!(FileQuery::new().length(..).document().unwrap(), FileQuery::new().document().unwrap());
+    }
+
+    #[test]
+    fn sorts_with_id_tiebreak() {
+        assert_eq!(FileQuery::new().sort(), doc! {"_id": 1});
+        let query = FileQuery::new()
+            .sort_by(FileSort::UploadDate, false)
+            .sort_by(FileSort::Filename, true);
+        assert_eq!(query.sort(), doc! {"uploadDate": -1, "filename": 1, "_id": 1});
+    }
+
+    #[test]
+    fn pages_without_overflow() {
+        let query = FileQuery::new().page(3, 20);
+        assert_eq!((query.skip, query.limit), (Some(60), Some(20)));
+        let query = FileQuery::new().page(u64::MAX, 2);
+        assert_eq!((query.skip, query.limit), (Some(u64::MAX), Some(2)));
+        let query = FileQuery::new().page(1, 0);
+        assert_eq!((query.skip, query.limit), (Some(1), Some(1)));
+    }
+
+    #[test]
+    fn clamps_limits() {
+        assert_eq!(FileQuery::new().limit(u64::MAX).limit, Some(i64::MAX));
     }
 }
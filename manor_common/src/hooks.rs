@@ -0,0 +1,61 @@
+use crate::error::MResult;
+
+/// Lifecycle hooks run by [crate::collection::Collection] around persistence. Every hook defaults to doing nothing, and returning an error aborts the operation.
+///
+/// `#[schema(...)]` generates an empty implementation, unless the schema opts into custom hooks with `#[schema(hooks)]`:
+///
+/// ```no_run
+/// use manor::{schema, Hooks, MResult, bson::Uuid};
+///
+/// #[schema(collection = "users", hooks)]
+/// pub struct User {
+///     #[field(id = Uuid::new)]
+///     pub id: Uuid,
+///     pub email: String,
+/// }
+///
+/// #[manor::async_trait::async_trait]
+/// impl Hooks for User {
+///     async fn before_insert(&mut self) -> MResult<()> {
+///         self.email = self.email.trim().to_lowercase();
+///         Ok(())
+///     }
+/// }
+/// ```
+#[async_trait::async_trait]
+pub trait Hooks: Send + Sync {
+    /// Runs before a document is inserted with `insert_one` or `insert_many`, before validation
+    async fn before_insert(&mut self) -> MResult<()> {
+        Ok(())
+    }
+
+    /// Runs after a document was inserted with `insert_one` or `insert_many`
+    async fn after_insert(&self) -> MResult<()> {
+        Ok(())
+    }
+
+    /// Runs before a document is saved with `save`, before validation
+    async fn before_save(&mut self) -> MResult<()> {
+        Ok(())
+    }
+
+    /// Runs after a document was saved with `save`
+    async fn after_save(&self) -> MResult<()> {
+        Ok(())
+    }
+
+    /// Runs before a document is deleted with `delete`. Deletions by query (ie `delete_many`) do not load documents, so they do not run hooks.
+    async fn before_delete(&self) -> MResult<()> {
+        Ok(())
+    }
+
+    /// Runs after a document was deleted with `delete`
+    async fn after_delete(&self) -> MResult<()> {
+        Ok(())
+    }
+
+    /// Runs when a document is loaded by a `find*` or `get*` operation (including every document yielded by a [crate::collection::Cursor] or [crate::watch::ChangeStream])
+    async fn after_load(&mut self) -> MResult<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+use bson::{Bson, Document};
+use futures_util::TryStreamExt;
+use mongodb::IndexModel;
+
+use crate::{
+    collection::Collection,
+    error::{Error, MResult, NAMESPACE_NOT_FOUND},
+    model::Model,
+};
+
+/// A report of the changes made (or required) by [Collection::sync_indexes]
+#[derive(Clone, Debug, Default)]
+pub struct IndexSync {
+    /// Names of declared indexes that were missing and have been created
+    pub created: Vec<String>,
+
+    /// Names of indexes present in the database that are either not declared by the model, or declared with different keys/options
+    pub stale: Vec<String>,
+
+    /// Names of stale indexes that were dropped
+    pub dropped: Vec<String>,
+}
+
+impl IndexSync {
+    /// Returns `true` if the database already matched the model's declared indexes
+    pub fn is_clean(&self) -> bool {
+        self.created.is_empty() && self.stale.is_empty()
+    }
+}
+
+/// An index on a model's collection declared by another model (ie the index on a link field generated by `#[backlink(...)]`).
+/// Registered with [inventory] by generated code, and included by [Collection::sync_indexes] alongside [Model::indexes].
+pub struct ForeignIndex {
+    /// Returns the name of the collection the index belongs to
+    pub collection: fn() -> String,
+
+    /// Returns the index to create
+    pub index: fn() -> IndexModel,
+}
+
+inventory::collect!(ForeignIndex);
+
+/// Returns an ascending index on a single (possibly nested) field. Generally only used by generated code.
+pub fn field_index(path: &str) -> IndexModel {
+    IndexModel::builder().keys(bson::doc! {path: 1}).build()
+}
+
+/// Returns the indexes declared for a model, including any [ForeignIndex]es registered against its collection
+pub fn declared_indexes<M: Model + Send + Sync>() -> Vec<IndexModel> {
+    let mut declared = M::indexes();
+    let collection = M::collection_name();
+    for foreign in inventory::iter::<ForeignIndex>().filter(|f| (f.collection)() == collection) {
+        let index = (foreign.index)();
+        if !declared.iter().any(|d| index_name(d) == index_name(&index)) {
+            declared.push(index);
+        }
+    }
+    declared
+}
+
+/// Returns the name of an index, generating it in the same format as the MongoDB driver if unset.
+pub(crate) fn index_name(index: &IndexModel) -> String {
+    if let Some(name) = index.options.as_ref().and_then(|o| o.name.clone()) {
+        return name;
+    }
+
+    index
+        .keys
+        .iter()
+        .map(|(key, value)| match value {
+            Bson::String(s) => format!("{key}_{s}"),
+            other => format!("{key}_{other}"),
+        })
+        .collect::<Vec<String>>()
+        .join("_")
+}
+
+/// Compares index keys, ignoring differences in numeric representation (ie `1` vs `1.0`)
+fn same_keys(left: &Document, right: &Document) -> bool {
+    fn normalize(value: &Bson) -> Bson {
+        match value {
+            Bson::Int32(v) => Bson::Int64(*v as i64),
+            Bson::Double(v) if v.fract() == 0.0 => Bson::Int64(*v as i64),
+            other => other.clone(),
+        }
+    }
+
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right.iter())
+            .all(|((lk, lv), (rk, rv))| lk == rk && normalize(lv) == normalize(rv))
+}
+
+/// Checks whether an existing index matches a declared one
+fn same_index(declared: &IndexModel, existing: &IndexModel) -> bool {
+    let unique = |i: &IndexModel| i.options.as_ref().and_then(|o| o.unique).unwrap_or(false);
+    let ttl = |i: &IndexModel| i.options.as_ref().and_then(|o| o.expire_after).map(|d| d.as_secs());
+
+    same_keys(&declared.keys, &existing.keys)
+        && unique(declared) == unique(existing)
+        && ttl(declared) == ttl(existing)
+}
+
+impl<M: Model + Send + Sync> Collection<M> {
+    /// Lists the indexes currently present on this collection. Returns an empty list if the collection does not exist yet.
+    pub async fn list_indexes(&self) -> MResult<Vec<IndexModel>> {
+        match self.collection().list_indexes().await {
+            Ok(cursor) => cursor.try_collect().await.map_err(Error::from),
+            Err(e) => match Error::from(e) {
+                e if e.server_code() == Some(NAMESPACE_NOT_FOUND) => Ok(Vec::new()),
+                e => Err(e),
+            },
+        }
+    }
+
+    /// Synchronizes this collection's indexes with those declared by [Model::indexes] (and by other models' backlinks, see [ForeignIndex]).
+    ///
+    /// Missing indexes are always created. Indexes that exist but are not declared (or are declared with different keys/options) are reported as stale,
+    /// and are only dropped (and recreated, if declared) when `drop_stale` is `true`. The default `_id_` index is never touched.
+    pub async fn sync_indexes(&self, drop_stale: bool) -> MResult<IndexSync> {
+        let declared = declared_indexes::<M>();
+        let existing = self.list_indexes().await?;
+        let mut report = IndexSync::default();
+        let mut to_create: Vec<IndexModel> = Vec::new();
+
+        for index in declared.iter() {
+            let name = index_name(index);
+            match existing.iter().find(|e| index_name(e) == name) {
+                None => to_create.push(index.clone()),
+                Some(current) if !same_index(index, current) => {
+                    report.stale.push(name.clone());
+                    if drop_stale {
+                        self.collection().drop_index(&name).await?;
+                        report.dropped.push(name);
+                        to_create.push(index.clone());
+                    }
+                }
+                Some(_) => (),
+            }
+        }
+
+        for index in existing.iter() {
+            let name = index_name(index);
+            if name == "_id_" || declared.iter().any(|d| index_name(d) == name) {
+                continue;
+            }
+
+            report.stale.push(name.clone());
+            if drop_stale {
+                self.collection().drop_index(&name).await?;
+                report.dropped.push(name);
+            }
+        }
+
+        if !to_create.is_empty() {
+            report.created = to_create.iter().map(index_name).collect();
+            self.collection().create_indexes(to_create).await?;
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bson::{Document, doc};
+    use mongodb::{IndexModel, options::IndexOptions};
+
+    use super::{index_name, same_index};
+
+    fn index(keys: Document, unique: Option<bool>, ttl: Option<u64>) -> IndexModel {
+        let options = IndexOptions::builder()
+            .unique(unique)
+            .expire_after(ttl.map(Duration::from_secs))
+            .build();
+        IndexModel::builder().keys(keys).options(options).build()
+    }
+
+    #[test]
+    fn names_like_the_driver() {
+        assert_eq!(index_name(&index(doc! {"username": 1, "created": -1}, None, None)), "username_1_created_-1");
+        assert_eq!(index_name(&index(doc! {"bio": "text"}, None, None)), "bio_text");
+        let named = IndexModel::builder()
+            .keys(doc! {"a": 1})
+            .options(IndexOptions::builder().name(String::from("custom")).build())
+            .build();
+        assert_eq!(index_name(&named), "custom");
+    }
+
+    #[test]
+    fn ignores_numeric_representation() {
+        assert!(same_index(
+            &index(doc! {"a": 1, "b": -1}, None, None),
+            &index(doc! {"a": 1_i64, "b": -1.0}, None, None)
+        ));
+    }
+
+    #[test]
+    fn compares_key_order_and_direction() {
+        let declared = index(doc! {"a": 1, "b": 1}, None, None);
+        assert!(!same_index(&declared, &index(doc! {"b": 1, "a": 1}, None, None)));
+        assert!(!same_index(&declared, &index(doc! {"a": 1, "b": -1}, None, None)));
+        assert!(!same_index(&declared, &index(doc! {"a": 1}, None, None)));
+    }
+
+    #[test]
+    fn compares_options() {
+        let declared = index(doc! {"a": 1}, Some(true), None);
+        assert!(same_index(&declared, &index(doc! {"a": 1}, Some(true), None)));
+        assert!(!same_index(&declared, &index(doc! {"a": 1}, None, None)));
+        assert!(same_index(&index(doc! {"a": 1}, Some(false), None), &index(doc! {"a": 1}, None, None)));
+
+        let declared = index(doc! {"a": 1}, None, Some(60));
+        assert!(same_index(&declared, &index(doc! {"a": 1}, None, Some(60))));
+        assert!(!same_index(&declared, &index(doc! {"a": 1}, None, Some(120))));
+        assert!(!same_index(&declared, &index(doc! {"a": 1}, None, None)));
+    }
+}
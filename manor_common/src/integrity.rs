@@ -0,0 +1,592 @@
+use std::collections::HashSet;
+
+use bson::{Bson, Document, doc, from_bson};
+use futures_util::{TryStreamExt, future::BoxFuture};
+use mongodb::{
+    error::{ErrorKind, GridFsErrorKind},
+    options::{DeleteOptions, FindOneAndDeleteOptions, FindOneOptions},
+};
+
+use crate::{
+    client::Client,
+    collection::{Collection, Ops, lookup, with_session},
+    error::{Error, MResult},
+    gridfs::FileLink,
+    model::Model,
+    session::Session,
+    soft_delete::{deletion, exclude_deleted},
+};
+
+/// Number of linking documents whose targets are looked up at once by [Collection::check_links]
+const CHECK_BATCH_SIZE: usize = 1000;
+
+/// How a field holds its [crate::types::Link]s
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkKind {
+    /// A required `Link<T>`
+    Single,
+
+    /// An `Option<Link<T>>`
+    Optional,
+
+    /// A `Vec<Link<T>>`
+    Many,
+}
+
+/// Describes a [crate::types::Link] field of a model. Generated by `#[schema(...)]`, see [Model::link_fields].
+#[derive(Clone, Debug)]
+pub struct LinkField {
+    /// Serialized path of the field
+    pub path: &'static str,
+
+    /// Returns the collection name of the linked model
+    pub target: fn() -> String,
+
+    /// Returns the `deleted_at` path of the linked model, if it was declared with `#[schema(soft_delete)]`
+    pub target_deleted: fn() -> Option<&'static str>,
+
+    /// How the field holds its links
+    pub kind: LinkKind,
+}
+
+/// How [Collection::check_links] should repair broken links
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repair {
+    /// Only report broken links
+    None,
+
+    /// Set broken `Option<Link<T>>` fields to `null` and remove broken entries from `Vec<Link<T>>` fields. Required `Link<T>` fields cannot be unset, and are left as-is.
+    Unset,
+
+    /// Delete documents holding broken links (applying their own delete policies)
+    Delete,
+}
+
+/// A link whose target document does not exist (or has been soft-deleted)
+#[derive(Clone, Debug)]
+pub struct BrokenLink {
+    /// ID of the document holding the link
+    pub id: Bson,
+
+    /// Serialized path of the link field
+    pub field: String,
+
+    /// ID of the missing (or soft-deleted) target document
+    pub target: Bson,
+
+    /// Whether the link was repaired
+    pub repaired: bool,
+}
+
+/// The result of [Collection::check_links]
+#[derive(Clone, Debug, Default)]
+pub struct IntegrityReport {
+    /// Number of links checked
+    pub checked: u64,
+
+    /// Links whose targets were missing or soft-deleted
+    pub broken: Vec<BrokenLink>,
+}
+
+impl IntegrityReport {
+    /// Returns `true` if no broken links were found
+    pub fn is_clean(&self) -> bool {
+        self.broken.is_empty()
+    }
+}
+
+/// What happens to documents linking to a deleted document, declared with `#[field(on_delete = "...")]`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnDelete {
+    /// Linking documents are deleted as well (applying their own policies in turn)
+    Cascade,
+
+    /// The link is set to `null` (for `Option<Link<M>>` fields) or removed from the array (for `Vec<Link<M>>` fields)
+    SetNull,
+
+    /// The deletion fails with [Error::Restricted] while any linking documents exist
+    Restrict,
+}
+
+/// Deletes the documents of a model matching a filter (within a [Session], if any), returning the files to delete once the deletion is committed
+pub type Cascade = fn(Client, Option<Session>, Document) -> BoxFuture<'static, MResult<Vec<FileLink>>>;
+
+/// A delete policy for a link field, registered with [inventory] by code generated from `#[field(on_delete = "...")]`
+pub struct LinkRule {
+    /// Returns the collection name of the linked (target) model
+    pub target: fn() -> String,
+
+    /// Returns the collection name of the model holding the link
+    pub source: fn() -> String,
+
+    /// Serialized path of the link field within the source model
+    pub path: &'static str,
+
+    /// Whether the field holds an array of links
+    pub many: bool,
+
+    /// The policy to apply
+    pub action: OnDelete,
+
+    /// Deletes matching documents of the source model, applying its own policies, and returns the files to delete once the deletion is committed.
+    /// Generated as [cascade]`::<Source>`.
+    pub cascade: Cascade,
+}
+
+inventory::collect!(LinkRule);
+
+/// A document blocking a deletion through an `on_delete = "restrict"` link
+#[derive(Clone, Debug)]
+pub struct Reference {
+    /// Collection of the linking document
+    pub collection: String,
+
+    /// Serialized path of the link field
+    pub field: String,
+
+    /// ID of the linking document
+    pub id: Bson,
+}
+
+/// Deletes every document of `S` matching `filter`, applying `S`'s own link policies. Generally only used by generated code.
+pub fn cascade<S: Model + Send + Sync>(
+    client: Client,
+    session: Option<Session>,
+    filter: Document,
+) -> BoxFuture<'static, MResult<Vec<FileLink>>> {
+    Box::pin(async move {
+        let collection = client.collection::<S>();
+        let collection = match &session {
+            Some(session) => collection.with_session(session),
+            None => collection,
+        };
+        match deletion::<S>() {
+            Some(update) => collection.soft_delete(filter, update, Ops::Many, None).await.map(|_| Vec::new()),
+            None => collection.delete_linked(filter, Ops::Many, None).await.map(|(_, files)| files),
+        }
+    })
+}
+
+/// Splits find-and-delete options into the options of the find & delete performed by [Collection::find_one_and_delete_enforced]
+fn find_delete_options(options: Option<FindOneAndDeleteOptions>) -> (Option<FindOneOptions>, Option<DeleteOptions>) {
+    let Some(o) = options else {
+        return (None, None);
+    };
+    let find = FindOneOptions::builder()
+        .max_time(o.max_time)
+        .projection(o.projection)
+        .sort(o.sort)
+        .collation(o.collation.clone())
+        .hint(o.hint)
+        .let_vars(o.let_vars.clone())
+        .comment(o.comment.clone())
+        .build();
+    let delete = DeleteOptions::builder()
+        .write_concern(o.write_concern)
+        .collation(o.collation)
+        .let_vars(o.let_vars)
+        .comment(o.comment)
+        .build();
+    (Some(find), Some(delete))
+}
+
+/// Deletes files unlinked by a committed deletion. Files that are already gone are ignored.
+pub(crate) async fn delete_files(client: &Client, files: Vec<FileLink>) -> MResult<()> {
+    for file in files {
+        let file = file.with_client(client.clone());
+        match file.fs().bucket().delete(file.id.into()).await {
+            Err(e) if matches!(*e.kind, ErrorKind::GridFs(GridFsErrorKind::FileNotFound { .. })) => (),
+            other => other?,
+        }
+    }
+    Ok(())
+}
+
+/// Returns `true` if a deletion that failed to run within a transaction should run again without one (see [Client::allow_non_atomic_deletes])
+fn non_atomic_fallback(allowed: bool, error: &Error) -> bool {
+    allowed && error.transactions_unsupported()
+}
+
+/// Selects the documents holding links in the field at `path`. Soft-deleted documents are skipped unless `include_deleted` is set (see [Collection::with_deleted]).
+fn linking_documents<M: Model + Send + Sync>(path: &str, include_deleted: bool) -> Document {
+    let filter = doc! {format!("{path}.id"): {"$exists": true}};
+    if include_deleted {
+        filter
+    } else {
+        exclude_deleted::<M>(filter)
+    }
+}
+
+/// Returns every registered [LinkRule] targeting the given collection
+pub fn link_rules(target: &str) -> Vec<&'static LinkRule> {
+    inventory::iter::<LinkRule>()
+        .filter(|rule| (rule.target)() == target)
+        .collect()
+}
+
+impl<M: Model + Send + Sync> Collection<M> {
+    fn raw_collection(&self, name: &str) -> mongodb::Collection<Document> {
+        self.client.database().collection::<Document>(name)
+    }
+
+    /// Runs a deletion that enforces the [LinkRule]s targeting this model. Runs within a transaction, unless already bound to a [Session].
+    /// If the server does not support transactions, fails with [Error::Unsupported] unless the client opted into non-atomic deletions (see [Client::allow_non_atomic_deletes]).
+    ///
+    /// Files linked by `#[field(delete_file)]` fields are deleted once the deletion is committed (see [Collection::delete_files]).
+    async fn enforced<T, F, Fut>(&self, operation: F) -> MResult<T>
+    where
+        F: Fn(Collection<M>) -> Fut,
+        Fut: Future<Output = MResult<(T, Vec<FileLink>)>>,
+    {
+        if self.session.is_some() {
+            let (value, files) = operation(self.clone()).await?;
+            self.delete_files(files).await?;
+            return Ok(value);
+        }
+
+        let (value, files) = if link_rules(&M::collection_name()).is_empty() {
+            operation(self.clone()).await?
+        } else {
+            let result = self.client.transaction(|txn| operation(self.with_session(&txn))).await;
+            match result {
+                Err(e) if non_atomic_fallback(self.client.non_atomic_deletes, &e) => operation(self.clone()).await?,
+                Err(e) if e.transactions_unsupported() => {
+                    return Err(Error::Unsupported(format!(
+                        "deletions from {} (targeted by on_delete policies) without transactions; use a replica set or Client::allow_non_atomic_deletes",
+                        M::collection_name()
+                    )));
+                }
+                other => other?,
+            }
+        };
+
+        self.delete_files(files).await?;
+        Ok(value)
+    }
+
+    /// Deletes files unlinked by a deletion through this collection. GridFS deletions cannot run within a [Session], so when this collection is bound to one,
+    /// the files are held by the session until its transaction is committed (see [Session::delete_files]).
+    pub(crate) async fn delete_files(&self, files: Vec<FileLink>) -> MResult<()> {
+        match &self.session {
+            Some(session) => {
+                session.files.lock().await.extend(files);
+                Ok(())
+            }
+            None => delete_files(&self.client, files).await,
+        }
+    }
+
+    /// Returns the files linked by `#[field(delete_file)]` fields of the documents matching `filter`
+    async fn linked_files(&self, filter: Document) -> MResult<Vec<FileLink>> {
+        let raw = self.raw_collection(&self.name());
+        let mut files: Vec<FileLink> = Vec::new();
+        for path in M::file_fields() {
+            let links = with_session!(self, raw.distinct(path, filter.clone()))?;
+            files.extend(links.into_iter().filter_map(|link| from_bson::<FileLink>(link).ok()));
+        }
+        Ok(files)
+    }
+
+    /// Returns the files linked by `#[field(delete_file)]` fields of the documents a bulk delete statement (`{"q": ..., "limit": ...}`) is about to remove.
+    /// Single deletions are narrowed to the found document's ID, so that the same document is removed.
+    pub(crate) async fn statement_files(&self, statement: &mut Document) -> MResult<Vec<FileLink>> {
+        if M::file_fields().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let query = statement.get_document("q").cloned().unwrap_or_default();
+        if statement.get_i32("limit") != Ok(1) {
+            return self.linked_files(query).await;
+        }
+        let raw = self.raw_collection(&self.name());
+        let found = with_session!(self, raw.find_one(query).projection(doc! {"_id": 1}))?;
+        match found.and_then(|d| d.get("_id").cloned()) {
+            Some(id) => {
+                statement.insert("q", doc! {"_id": id.clone()});
+                self.linked_files(doc! {"_id": id}).await
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Deletes documents, first enforcing the [LinkRule]s targeting this model (see [Collection::enforced])
+    pub(crate) async fn delete_enforced(
+        &self,
+        query: Document,
+        operations: Ops,
+        options: Option<DeleteOptions>,
+    ) -> MResult<u64> {
+        self.enforced(|collection| {
+            let (query, operations, options) = (query.clone(), operations.clone(), options.clone());
+            async move { collection.delete_linked(query, operations, options).await }
+        })
+        .await
+    }
+
+    /// Finds one document and deletes it, enforcing the [LinkRule]s targeting this model (see [Collection::enforced]). Returns the deleted document.
+    pub(crate) async fn find_one_and_delete_enforced(
+        &self,
+        query: Document,
+        options: Option<FindOneAndDeleteOptions>,
+    ) -> MResult<Option<M>> {
+        let (find, delete) = find_delete_options(options);
+        self.enforced(|collection| {
+            let (query, find, delete) = (query.clone(), find.clone(), delete.clone());
+            async move {
+                // The document is read within the same transaction, so it is the one deleted
+                let typed = collection.collection();
+                let action = typed.find_one(query).with_options(find);
+                let Some(record) = with_session!(collection, action)? else {
+                    return Ok((None, Vec::new()));
+                };
+                let (_, files) = collection.delete_linked(doc! {"_id": record.id()}, Ops::One, delete).await?;
+                Ok((Some(record), files))
+            }
+        })
+        .await
+    }
+
+    /// Deletes documents and applies the [LinkRule]s targeting this model, within this collection's [Session] (if any).
+    /// Returns the number of deleted documents, and the files linked by `#[field(delete_file)]` fields of the deleted documents (including cascaded ones).
+    async fn delete_linked(
+        &self,
+        query: Document,
+        operations: Ops,
+        options: Option<DeleteOptions>,
+    ) -> MResult<(u64, Vec<FileLink>)> {
+        let raw = self.raw_collection(&self.name());
+        let rules = link_rules(&M::collection_name());
+        if rules.is_empty() && M::file_fields().is_empty() {
+            let action = match operations {
+                Ops::Many => raw.delete_many(query).with_options(options),
+                Ops::One => raw.delete_one(query).with_options(options),
+            };
+            return with_session!(self, action)
+                .map(|r| (r.deleted_count, Vec::new()))
+                .map_err(Error::from);
+        }
+
+        let ids: Vec<Bson> = match operations {
+            Ops::Many => with_session!(self, raw.distinct("_id", query))?,
+            Ops::One => with_session!(self, raw.find_one(query).projection(doc! {"_id": 1}))?
+                .and_then(|d| d.get("_id").cloned())
+                .into_iter()
+                .collect(),
+        };
+        if ids.is_empty() {
+            return Ok((0, Vec::new()));
+        }
+
+        let linking = |rule: &LinkRule| doc! {format!("{}.id", rule.path): {"$in": ids.clone()}};
+        let mut blocking: Vec<Reference> = Vec::new();
+        for rule in rules.iter().filter(|r| r.action == OnDelete::Restrict) {
+            let source = (rule.source)();
+            let found = with_session!(self, self.raw_collection(&source).distinct("_id", linking(rule)))?;
+            blocking.extend(found.into_iter().map(|id| Reference {
+                collection: source.clone(),
+                field: rule.path.to_string(),
+                id,
+            }));
+        }
+        if !blocking.is_empty() {
+            return Err(Error::Restricted(blocking));
+        }
+
+        let mut files = self.linked_files(doc! {"_id": {"$in": ids.clone()}}).await?;
+
+        // Targets are removed before cascading, so that cyclic cascades terminate
+        let action = raw
+            .delete_many(doc! {"_id": {"$in": ids.clone()}})
+            .with_options(options);
+        let deleted = with_session!(self, action)?.deleted_count;
+
+        for rule in rules {
+            match rule.action {
+                OnDelete::Restrict => (),
+                OnDelete::SetNull => {
+                    let update = if rule.many {
+                        doc! {"$pull": {rule.path: {"id": {"$in": ids.clone()}}}}
+                    } else {
+                        doc! {"$set": {rule.path: Bson::Null}}
+                    };
+                    let source = self.raw_collection(&(rule.source)());
+                    with_session!(self, source.update_many(linking(rule), update))?;
+                }
+                OnDelete::Cascade => {
+                    files.extend((rule.cascade)(self.client.clone(), self.session.clone(), linking(rule)).await?);
+                }
+            }
+        }
+
+        Ok((deleted, files))
+    }
+}
+
+impl<M: Model + Send + Sync> Collection<M> {
+    /// Scans every [crate::types::Link] field of this model (see [Model::link_fields]) for links whose target no longer exists, optionally repairing them.
+    /// Links to soft-deleted documents (see `#[schema(soft_delete)]`) are reported as broken as well.
+    ///
+    /// Like finds, the scan skips this model's own soft-deleted documents, so they are neither reported nor repaired. Call this on [Collection::with_deleted] to include them.
+    ///
+    /// Targets are looked up in batches with `$in` queries, and repairs are applied once each field has been fully scanned.
+    /// The scan itself does not run within this collection's [Session], but repairs do.
+    pub async fn check_links(&self, repair: Repair) -> MResult<IntegrityReport> {
+        let mut report = IntegrityReport::default();
+        for field in M::link_fields() {
+            let filter = linking_documents::<M>(field.path, self.include_deleted);
+            let mut cursor = self
+                .raw_collection(&self.name())
+                .find(filter)
+                .projection(doc! {"_id": 1, field.path: 1})
+                .await?;
+
+            let mut broken: Vec<BrokenLink> = Vec::new();
+            let mut batch: Vec<(Bson, Vec<Bson>)> = Vec::new();
+            loop {
+                let next = cursor.try_next().await?;
+                if let Some(document) = next.as_ref() {
+                    let targets = match lookup(document, field.path) {
+                        Bson::Array(links) => links
+                            .iter()
+                            .filter_map(|l| l.as_document().and_then(|l| l.get("id")).cloned())
+                            .collect(),
+                        Bson::Document(link) => link.get("id").cloned().into_iter().collect(),
+                        _ => Vec::new(),
+                    };
+                    batch.push((document.get("_id").cloned().unwrap_or(Bson::Null), targets));
+                }
+
+                if batch.len() >= CHECK_BATCH_SIZE || (next.is_none() && !batch.is_empty()) {
+                    broken.extend(self.check_batch(&field, std::mem::take(&mut batch), &mut report).await?);
+                }
+                if next.is_none() {
+                    break;
+                }
+            }
+
+            // Repairs modify the scanned collection, so they only start once the scan's cursor is exhausted
+            for chunk in broken.chunks(CHECK_BATCH_SIZE) {
+                self.repair_links(&field, chunk.to_vec(), repair, &mut report).await?;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Looks up the targets of a batch of linking documents, returning the broken links
+    async fn check_batch(
+        &self,
+        field: &LinkField,
+        batch: Vec<(Bson, Vec<Bson>)>,
+        report: &mut IntegrityReport,
+    ) -> MResult<Vec<BrokenLink>> {
+        let targets: Vec<Bson> = batch.iter().flat_map(|(_, t)| t.iter().cloned()).collect();
+        report.checked += targets.len() as u64;
+
+        let mut filter = doc! {"_id": {"$in": targets}};
+        if let Some(deleted) = (field.target_deleted)() {
+            filter.insert(deleted, Bson::Null);
+        }
+        let existing: HashSet<String> = self
+            .raw_collection(&(field.target)())
+            .distinct("_id", filter)
+            .await?
+            .iter()
+            .map(Bson::to_string)
+            .collect();
+
+        Ok(batch
+            .into_iter()
+            .flat_map(|(id, targets)| {
+                targets
+                    .into_iter()
+                    .filter(|t| !existing.contains(&t.to_string()))
+                    .map(move |target| BrokenLink {
+                        id: id.clone(),
+                        field: field.path.to_string(),
+                        target,
+                        repaired: false,
+                    })
+            })
+            .collect())
+    }
+
+    /// Repairs broken links as requested, adding them to the report
+    async fn repair_links(
+        &self,
+        field: &LinkField,
+        mut broken: Vec<BrokenLink>,
+        repair: Repair,
+        report: &mut IntegrityReport,
+    ) -> MResult<()> {
+        let ids: Vec<Bson> = broken.iter().map(|b| b.id.clone()).collect();
+        let missing: Vec<Bson> = broken.iter().map(|b| b.target.clone()).collect();
+        let repaired = match (repair, field.kind) {
+            _ if broken.is_empty() => false,
+            (Repair::None, _) | (Repair::Unset, LinkKind::Single) => false,
+            (Repair::Unset, kind) => {
+                let update = if kind == LinkKind::Many {
+                    doc! {"$pull": {field.path: {"id": {"$in": missing.clone()}}}}
+                } else {
+                    doc! {"$set": {field.path: Bson::Null}}
+                };
+                let filter = self.scoped(doc! {"_id": {"$in": ids}, format!("{}.id", field.path): {"$in": missing}});
+                let collection = self.raw_collection(&self.name());
+                with_session!(self, collection.update_many(filter, update))?;
+                true
+            }
+            (Repair::Delete, _) => {
+                self.delete_many(doc! {"_id": {"$in": ids}}).await?;
+                true
+            }
+        };
+
+        for link in broken.iter_mut() {
+            link.repaired = repaired;
+        }
+        report.broken.extend(broken);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+
+    use crate::{error::Error, testing::Record};
+
+    use super::{linking_documents, non_atomic_fallback};
+
+    /// Returns the error the driver reports when a session cannot start a transaction, without contacting a server
+    fn unsupported() -> Error {
+        let client = mongodb::sync::Client::with_uri_str("mongodb://localhost:1/").unwrap();
+        let mut session = client.start_session().snapshot(true).run().unwrap();
+        session.start_transaction().run().unwrap_err().into()
+    }
+
+    #[test]
+    fn falls_back_when_the_driver_refuses_transactions() {
+        let error = unsupported();
+        assert!(error.server_code().is_none());
+        assert!(non_atomic_fallback(true, &error));
+    }
+
+    #[test]
+    fn requires_opting_into_non_atomic_deletes() {
+        assert!(!non_atomic_fallback(false, &unsupported()));
+    }
+
+    #[test]
+    fn other_failures_do_not_fall_back() {
+        assert!(!non_atomic_fallback(true, &Error::NotFound));
+        assert!(!non_atomic_fallback(true, &Error::Unsupported(String::from("bulk delete"))));
+    }
+
+    #[test]
+    fn scans_links_of_documents_that_are_not_deleted() {
+        assert_eq!(
+            linking_documents::<Record>("owner", false),
+            doc! {"owner.id": {"$exists": true}, "deleted_at": null}
+        );
+        assert_eq!(linking_documents::<Record>("owner", true), doc! {"owner.id": {"$exists": true}});
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+};
+
+use bson::{Document, doc, to_bson};
+use mongodb::options::{ValidationAction, ValidationLevel};
+
+use crate::{
+    collection::Collection,
+    error::{Error, MResult, NAMESPACE_NOT_FOUND},
+    gridfs::FileLink,
+    model::Model,
+    types::Link,
+};
+
+/// Types with a known `$jsonSchema` description of their BSON representation.
+///
+/// Implemented for common primitive, collection & BSON types, and derived for models by `#[schema(...)]` (see [Model::json_schema]) and for nested structs by `#[derive(BsonSchema)]`.
+pub trait BsonSchema {
+    /// Returns the `$jsonSchema` describing this type
+    fn bson_schema() -> Document;
+}
+
+macro_rules! impl_bson_type {
+    ($bson_type:literal: $($ty:ty),*) => {
+        $(
+            impl BsonSchema for $ty {
+                fn bson_schema() -> Document {
+                    doc! {"bsonType": $bson_type}
+                }
+            }
+        )*
+    };
+}
+
+impl_bson_type!("string": String, str, char);
+impl_bson_type!("bool": bool);
+impl_bson_type!("int": i8, i16, i32, u8, u16);
+impl_bson_type!("long": i64, u32, u64, usize, isize);
+impl_bson_type!("double": f32, f64);
+impl_bson_type!("objectId": bson::oid::ObjectId);
+impl_bson_type!("date": bson::DateTime);
+impl_bson_type!("binData": bson::Uuid, bson::Binary, uuid::Uuid);
+impl_bson_type!("object": Document);
+impl_bson_type!("timestamp": bson::Timestamp);
+impl_bson_type!("decimal": bson::Decimal128);
+
+impl BsonSchema for bson::Bson {
+    fn bson_schema() -> Document {
+        Document::new()
+    }
+}
+
+impl<Tz: chrono::TimeZone> BsonSchema for chrono::DateTime<Tz> {
+    fn bson_schema() -> Document {
+        doc! {"bsonType": "string"}
+    }
+}
+
+impl<T: BsonSchema> BsonSchema for Option<T> {
+    fn bson_schema() -> Document {
+        let mut schema = T::bson_schema();
+        let null = bson::Bson::from("null");
+        match schema.get_mut("bsonType") {
+            Some(bson::Bson::String(bson_type)) => {
+                let bson_type = bson::Bson::from(std::mem::take(bson_type));
+                schema.insert("bsonType", vec![bson_type, null]);
+                schema
+            }
+            // Already nullable (ie a nested Option)
+            Some(bson::Bson::Array(types)) if types.contains(&null) => schema,
+            Some(bson::Bson::Array(types)) => {
+                types.push(null);
+                schema
+            }
+            _ => doc! {"anyOf": [schema, {"bsonType": "null"}]},
+        }
+    }
+}
+
+impl<T: BsonSchema> BsonSchema for Vec<T> {
+    fn bson_schema() -> Document {
+        doc! {"bsonType": "array", "items": T::bson_schema()}
+    }
+}
+
+impl<T: BsonSchema, S> BsonSchema for HashMap<String, T, S> {
+    fn bson_schema() -> Document {
+        doc! {"bsonType": "object", "additionalProperties": T::bson_schema()}
+    }
+}
+
+impl<T: BsonSchema> BsonSchema for BTreeMap<String, T> {
+    fn bson_schema() -> Document {
+        doc! {"bsonType": "object", "additionalProperties": T::bson_schema()}
+    }
+}
+
+impl<T: BsonSchema + ?Sized> BsonSchema for Box<T> {
+    fn bson_schema() -> Document {
+        T::bson_schema()
+    }
+}
+
+impl<M: Model + Send + Sync> BsonSchema for Link<M> {
+    fn bson_schema() -> Document {
+        doc! {
+            "bsonType": "object",
+            "required": ["collection", "id"],
+            "properties": {"collection": {"bsonType": "string"}}
+        }
+    }
+}
+
+impl BsonSchema for FileLink {
+    fn bson_schema() -> Document {
+        doc! {
+            "bsonType": "object",
+            "required": ["bucket", "id"],
+            "properties": {"bucket": {"bsonType": "string"}, "id": {"bsonType": "binData"}}
+        }
+    }
+}
+
+/// Resolves the schema of a field type, falling back to an unconstrained schema for types that do not implement [BsonSchema].
+/// Generally only used by generated code, as `(&&SchemaOf::<T>::new()).schema()` with [SchemaKnown] and [SchemaFallback] in scope.
+#[doc(hidden)]
+pub struct SchemaOf<T: ?Sized>(PhantomData<fn() -> Box<T>>);
+
+impl<T: ?Sized> SchemaOf<T> {
+    #[allow(missing_docs, clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[doc(hidden)]
+#[allow(missing_docs)]
+pub trait SchemaKnown {
+    fn schema(&self) -> Document;
+}
+
+impl<T: BsonSchema + ?Sized> SchemaKnown for &SchemaOf<T> {
+    fn schema(&self) -> Document {
+        T::bson_schema()
+    }
+}
+
+#[doc(hidden)]
+#[allow(missing_docs)]
+pub trait SchemaFallback {
+    fn schema(&self) -> Document;
+}
+
+impl<T: ?Sized> SchemaFallback for SchemaOf<T> {
+    fn schema(&self) -> Document {
+        Document::new()
+    }
+}
+
+/// Builds an object schema from `(name, schema, required)` properties. Generally only used by generated code.
+pub fn object_schema(properties: impl IntoIterator<Item = (&'static str, Document, bool)>) -> Document {
+    let mut schema = Document::new();
+    let mut required: Vec<&str> = Vec::new();
+    for (name, property, is_required) in properties {
+        if is_required {
+            required.push(name);
+        }
+        schema.insert(name, property);
+    }
+
+    let mut object = doc! {"bsonType": "object"};
+    if !required.is_empty() {
+        object.insert("required", required);
+    }
+    object.insert("properties", schema);
+    object
+}
+
+impl<M: Model + Send + Sync> Collection<M> {
+    /// Applies this model's `$jsonSchema` (see [Model::json_schema]) as the collection's server-side validator, using `collMod` (or `createCollection`, if the collection does not exist yet).
+    ///
+    /// `level` controls which writes are validated, and `action` whether invalid documents are rejected or only logged.
+    pub async fn apply_validator(&self, level: ValidationLevel, action: ValidationAction) -> MResult<()> {
+        let database = self.client.database();
+        let validator = doc! {"$jsonSchema": M::json_schema()};
+        let command = doc! {
+            "collMod": self.name(),
+            "validator": validator.clone(),
+            "validationLevel": to_bson(&level)?,
+            "validationAction": to_bson(&action)?
+        };
+
+        match database.run_command(command).await {
+            Ok(_) => Ok(()),
+            Err(e) => match Error::from(e) {
+                e if e.server_code() == Some(NAMESPACE_NOT_FOUND) => database
+                    .create_collection(self.name())
+                    .validator(validator)
+                    .validation_level(level)
+                    .validation_action(action)
+                    .await
+                    .map_err(Error::from),
+                e => Err(e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{Bson, doc};
+
+    use super::{BsonSchema, SchemaFallback, SchemaKnown, SchemaOf, object_schema};
+
+    #[test]
+    fn describes_options() {
+        assert_eq!(Option::<i32>::bson_schema(), doc! {"bsonType": ["int", "null"]});
+        assert_eq!(
+            Option::<Vec<String>>::bson_schema(),
+            doc! {"bsonType": ["array", "null"], "items": {"bsonType": "string"}}
+        );
+        assert_eq!(Option::<Bson>::bson_schema(), doc! {"anyOf": [{}, {"bsonType": "null"}]});
+        assert_eq!(Option::<Option<i64>>::bson_schema(), doc! {"bsonType": ["long", "null"]});
+    }
+
+    #[test]
+    fn describes_vectors() {
+        assert_eq!(Vec::<u8>::bson_schema(), doc! {"bsonType": "array", "items": {"bsonType": "int"}});
+        assert_eq!(
+            Vec::<Option<String>>::bson_schema(),
+            doc! {"bsonType": "array", "items": {"bsonType": ["string", "null"]}}
+        );
+        assert_eq!(
+            Vec::<Vec<f64>>::bson_schema(),
+            doc! {"bsonType": "array", "items": {"bsonType": "array", "items": {"bsonType": "double"}}}
+        );
+    }
+
+    #[test]
+    fn describes_nested_objects() {
+        let address = object_schema([
+            ("street", String::bson_schema(), true),
+            ("postcode", Option::<String>::bson_schema(), false),
+        ]);
+        assert_eq!(
+            object_schema([("name", String::bson_schema(), true), ("address", address, false)]),
+            doc! {
+                "bsonType": "object",
+                "required": ["name"],
+                "properties": {
+                    "name": {"bsonType": "string"},
+                    "address": {
+                        "bsonType": "object",
+                        "required": ["street"],
+                        "properties": {
+                            "street": {"bsonType": "string"},
+                            "postcode": {"bsonType": ["string", "null"]}
+                        }
+                    }
+                }
+            }
+        );
+        assert_eq!(
+            object_schema([("tag", Option::<String>::bson_schema(), false)]),
+            doc! {"bsonType": "object", "properties": {"tag": {"bsonType": ["string", "null"]}}}
+        );
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrow)]
+    fn leaves_unknown_types_unconstrained() {
+        struct Unknown;
+        assert_eq!((&&SchemaOf::<i32>::new()).schema(), doc! {"bsonType": "int"});
+        assert_eq!((&&SchemaOf::<Unknown>::new()).schema(), doc! {});
+    }
+}
@@ -5,6 +5,9 @@
 /// Submodule containing the [model::Model] trait
 pub mod model;
 
+/// Submodule containing the [hooks::Hooks] trait, used to run code around persistence
+pub mod hooks;
+
 /// Submodule containing a wrapper around [mongodb::Collection]
 pub mod collection;
 
@@ -14,20 +17,76 @@ pub mod error;
 /// Submodule containing the [client::Client] wrapper
 pub mod client;
 
+/// Submodule containing the [session::Session] wrapper, used for transactions
+pub mod session;
+
 /// Submodule containing the [types::Link] type and associated methods
 pub mod types;
 
 /// Submodule containing GridFS-related operations
 pub mod gridfs;
 
+/// Submodule containing typed [query::Field] descriptors and the [query::Query] builder
+pub mod query;
+
+/// Submodule containing the typed [update::Update] builder
+pub mod update;
+
+/// Submodule containing dirty tracking for loaded models, used by [model::Model::save_changes]
+pub mod changes;
+
+/// Submodule containing the typed [bulk::BulkWrite] builder
+pub mod bulk;
+
+/// Submodule containing the [populate::FindMany] builder, used to populate [types::Link]s with `$lookup`
+pub mod populate;
+
+/// Submodule containing the [projection::Projection] trait, used to load partial documents
+pub mod projection;
+
+/// Submodule containing offset & keyset pagination ([paginate::Paginate])
+pub mod paginate;
+
+/// Submodule containing typed change streams ([watch::ChangeStream])
+pub mod watch;
+
+/// Submodule containing referential integrity policies for [types::Link] fields
+pub mod integrity;
+
+/// Submodule containing the validation helpers used by `#[field(validate(...))]`
+pub mod validate;
+
+/// Submodule containing the [json_schema::BsonSchema] trait, used to generate server-side validators
+pub mod json_schema;
+
+/// Submodule containing the [timestamps::Timestamp] trait, used by automatic `created_at` & `updated_at` fields
+pub mod timestamps;
+
+/// Submodule containing soft deletion for models declared with `#[schema(soft_delete)]`
+pub mod soft_delete;
+
+/// Submodule containing optimistic concurrency control for models with a `#[field(version)]` field
+pub mod version;
+
+/// Submodule containing index synchronization for [model::Model] declared indexes
+pub mod index;
+
+/// Shared fixtures for unit tests
+#[cfg(test)]
+mod testing;
+
 /// Global instance of the Client, set using
 /// 
-/// ```
-/// Client::connect_with_*().as_global();
+/// ```no_run
+/// # use manor_common::{client::Client, error::MResult};
+/// # async fn connect() -> MResult<()> {
+/// Client::connect_with_uri("mongodb://localhost:27017/", "database").await?.as_global();
+/// # Ok(())
+/// # }
 /// ```
 pub(crate) use client::MANOR_CLIENT;
 
 #[doc(hidden)]
 pub use {
-    serde, bson, uuid, derive_builder
+    serde, bson, uuid, derive_builder, mongodb, inventory, once_cell, regex, async_trait
 };
\ No newline at end of file
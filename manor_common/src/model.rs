@@ -1,12 +1,13 @@
 use std::fmt::Debug;
 use bson::Bson;
+use mongodb::IndexModel;
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::{collection::Collection, error::MResult, MANOR_CLIENT};
+use crate::{collection::{Collection, UpsertResult}, error::MResult, hooks::Hooks, integrity::LinkField, session::Session, MANOR_CLIENT};
 
 /// A model trait. Likely should not be directly implemented, but instead generated with the `#[schema(...)]` attribute.
 #[async_trait::async_trait]
-pub trait Model: Serialize + DeserializeOwned + Clone + Debug + Send + Sync {
+pub trait Model: Serialize + DeserializeOwned + Clone + Debug + Hooks + Send + Sync + 'static {
     /// The type of this Model's `_id` field. A [bson::oid::ObjectId], [uuid::Uuid], or [String] are probably the best choices here, and [bson::oid::ObjectId] is the macro default.
     type Id: DeserializeOwned + Serialize + Clone + Debug + Send + Sync + Into<Bson>;
 
@@ -28,6 +29,82 @@ pub trait Model: Serialize + DeserializeOwned + Clone + Debug + Send + Sync {
     /// Sets the local collection
     fn attach_collection(&mut self, collection: Collection<Self>) -> ();
 
+    /// Returns the indexes declared for this model, used by [Collection::sync_indexes]. Generated from `#[field(index)]`, `#[field(unique)]`, `#[field(ttl = "...")]` and `#[schema(index(...))]`.
+    fn indexes() -> Vec<IndexModel> {
+        Vec::new()
+    }
+
+    /// Checks the validation rules declared with `#[field(validate(...))]`, returning [crate::error::Error::Validation] listing every failed rule.
+    /// Called before inserting or replacing documents, and by generated builders.
+    fn validate(&self) -> MResult<()> {
+        Ok(())
+    }
+
+    /// Returns a `$jsonSchema` describing this model's BSON representation, used by [Collection::apply_validator]. Generated by `#[schema(...)]`.
+    fn json_schema() -> bson::Document {
+        bson::Document::new()
+    }
+
+    /// Returns the serialized paths of this model's `#[field(created_at)]` and `#[field(updated_at)]` fields, if any
+    fn timestamp_fields() -> (Option<&'static str>, Option<&'static str>) {
+        (None, None)
+    }
+
+    /// Returns the current time, serialized as the values of this model's timestamp fields (keyed by path)
+    fn timestamps() -> MResult<bson::Document> {
+        Ok(bson::Document::new())
+    }
+
+    /// Sets this model's `updated_at` field (and `created_at`, if `created` is `true` and it is unset) to the current time. Called automatically before writes.
+    fn touch(&mut self, created: bool) {
+        let _ = created;
+    }
+
+    /// Returns the serialized path of this model's `#[field(version)]` field, if any
+    fn version_field() -> Option<&'static str> {
+        None
+    }
+
+    /// Returns this document's version, if the model has a `#[field(version)]` field
+    fn version(&self) -> Option<i64> {
+        None
+    }
+
+    /// Sets this document's version, if the model has a `#[field(version)]` field
+    fn set_version(&mut self, version: i64) {
+        let _ = version;
+    }
+
+    /// Returns the serialized path of this model's `deleted_at` field, if it was declared with `#[schema(soft_delete)]`
+    fn soft_delete_field() -> Option<&'static str> {
+        None
+    }
+
+    /// Returns `true` if documents record a snapshot when loaded or saved, used by [Model::save_changes]. Enabled with `#[schema(track_changes)]`.
+    fn tracks_changes() -> bool {
+        false
+    }
+
+    /// Returns the serialized state of this document when it was loaded or last saved, used by [Model::save_changes]
+    fn snapshot(&self) -> Option<&bson::Document> {
+        None
+    }
+
+    /// Sets this document's snapshot. Called automatically when documents are loaded through a [Collection] or [crate::collection::Cursor].
+    fn set_snapshot(&mut self, snapshot: Option<bson::Document>) {
+        let _ = snapshot;
+    }
+
+    /// Returns the serialized paths of this model's `#[field(delete_file)]` fields, whose linked [crate::gridfs::FileLink] files are deleted along with the document
+    fn file_fields() -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Returns descriptors for this model's [crate::types::Link] fields, used by [Collection::check_links]. Generated by `#[schema(...)]`.
+    fn link_fields() -> Vec<LinkField> {
+        Vec::new()
+    }
+
     /// Gets the local collection if present, otherwise attempts to use the global client. Panics if neither is defined.
     fn collection(&self) -> Collection<Self> {
         if let Some(coll) = self.own_collection() {
@@ -37,13 +114,31 @@ pub trait Model: Serialize + DeserializeOwned + Clone + Debug + Send + Sync {
         }
     }
 
-    /// Utility function to update/save this record in the database
-    async fn save(&self) -> MResult<()> {
-        self.collection().save(self.clone()).await
+    /// Utility function to update/save this record in the database, as a single atomic upsert. Increments this record's version, if the model has a `#[field(version)]` field.
+    ///
+    /// The saved state (including changes made by [Hooks::before_save], timestamps and the version) is written back to this record.
+    async fn save(&mut self) -> MResult<UpsertResult<Self>> {
+        self.collection().save_in_place(self).await
+    }
+
+    /// Utility function to write only the fields changed since this record was loaded (or last saved), as a single `$set`/`$unset` update.
+    /// Records that were not loaded through a [Collection] (or whose model does not track changes, see [Model::tracks_changes]) are saved in full. Returns `false` if nothing had changed.
+    async fn save_changes(&mut self) -> MResult<bool> {
+        self.collection().save_changes(self).await
     }
 
-    /// Utility function to delete this record from the database. Drops the Model instance.
+    /// Utility function to delete this record from the database (or mark it as deleted, for `#[schema(soft_delete)]` models). Drops the Model instance.
     async fn delete(self) -> MResult<()> {
         self.collection().delete(self).await
     }
+
+    /// Utility function to update/save this record within a [Session] (ie inside [crate::client::Client::transaction]). Increments this record's version, like [Model::save].
+    async fn save_with_session(&mut self, session: &Session) -> MResult<UpsertResult<Self>> {
+        self.collection().with_session(session).save_in_place(self).await
+    }
+
+    /// Utility function to delete this record within a [Session] (ie inside [crate::client::Client::transaction]). Drops the Model instance.
+    async fn delete_with_session(self, session: &Session) -> MResult<()> {
+        self.collection().with_session(session).delete(self).await
+    }
 }
\ No newline at end of file
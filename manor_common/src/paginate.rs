@@ -0,0 +1,268 @@
+use std::marker::PhantomData;
+
+use bson::{Bson, Document, doc, from_slice, to_vec};
+use futures_util::TryStreamExt;
+use mongodb::options::FindOptions;
+
+use crate::{
+    collection::{Collection, Find, lookup, serialize},
+    error::{Error, MResult},
+    model::Model,
+    query::{Field, IntoQuery},
+};
+
+/// How a page of results should be selected
+#[derive(Clone, Debug)]
+enum PageKind {
+    Offset(u64),
+    Keyset(Option<String>),
+}
+
+/// Describes a page of results to fetch with [Collection::paginate].
+///
+/// Offset pages are addressed by number and include the total number of matching documents. Keyset (cursor) pages are addressed by an opaque continuation token,
+/// and remain stable when documents are inserted or removed between requests.
+#[derive(Clone, Debug)]
+pub struct Paginate<M> {
+    kind: PageKind,
+    per_page: u64,
+    sort: String,
+    ascending: bool,
+    _marker: PhantomData<fn() -> M>,
+}
+
+impl<M> Paginate<M> {
+    /// Selects page number `page` (starting at `0`), with `per_page` results per page
+    pub fn offset(page: u64, per_page: u64) -> Self {
+        Self {
+            kind: PageKind::Offset(page),
+            per_page: per_page.max(1),
+            sort: String::from("_id"),
+            ascending: true,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Selects up to `per_page` results following the page that returned the `after` token ([Page::next]), or the first page if `after` is [None]
+    pub fn keyset(per_page: u64, after: Option<String>) -> Self {
+        Self {
+            kind: PageKind::Keyset(after),
+            per_page: per_page.max(1),
+            sort: String::from("_id"),
+            ascending: true,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Orders results by `field` (then by `_id`, to break ties). Defaults to ascending `_id` order.
+    pub fn sort_by<T>(mut self, field: Field<M, T>, ascending: bool) -> Self {
+        self.sort = field.path().to_string();
+        self.ascending = ascending;
+        self
+    }
+}
+
+/// A single page of results returned by [Collection::paginate]
+#[derive(Clone, Debug)]
+pub struct Page<M: Model + Send + Sync> {
+    /// The documents in this page
+    pub items: Vec<M>,
+
+    /// The total number of matching documents. Only computed for offset pages.
+    pub total: Option<u64>,
+
+    /// An opaque token to pass to [Paginate::keyset] to fetch the following page. Only returned for keyset pages, and [None] if this is the last page.
+    /// Tokens are only valid for the same sort field & direction.
+    pub next: Option<String>,
+}
+
+/// Encodes the position of the last document in a page as an opaque token, along with the sort order it was created for
+fn encode_token(sort: &str, ascending: bool, value: Bson, id: Bson) -> MResult<String> {
+    let bytes = to_vec(&doc! {"k": sort, "d": if ascending { 1 } else { -1 }, "v": value, "id": id})?;
+    Ok(hex::encode(bytes))
+}
+
+/// Decodes a token produced by [encode_token], checking that it was created for the same sort key & direction
+fn decode_token(sort: &str, ascending: bool, token: &str) -> MResult<(Bson, Bson)> {
+    let invalid = || Error::InvalidPageToken(token.to_string());
+    let bytes = hex::decode(token).map_err(|_| invalid())?;
+    let mut decoded: Document = from_slice(&bytes).map_err(|_| invalid())?;
+
+    let direction = if ascending { 1 } else { -1 };
+    match (decoded.remove("k"), decoded.remove("d"), decoded.remove("v"), decoded.remove("id")) {
+        (Some(Bson::String(key)), Some(Bson::Int32(d)), Some(value), Some(id)) if key == sort && d == direction => Ok((value, id)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Builds the filter selecting documents after the position `(value, id)` in the given sort order.
+///
+/// Null & missing values sort before every other value, but are not matched by `$gt`/`$lt`, so they are handled explicitly.
+/// Comparisons only match values of the same BSON type as `value`, so sort fields should hold a single type (or null).
+fn position(sort: &str, ascending: bool, value: Bson, id: Bson) -> Document {
+    let operator = if ascending { "$gt" } else { "$lt" };
+    if sort == "_id" {
+        return doc! {"_id": {operator: id}};
+    }
+
+    match (value, ascending) {
+        (Bson::Null, true) => doc! {"$or": [
+            {sort: Bson::Null, "_id": {operator: id}},
+            {sort: {"$ne": Bson::Null}}
+        ]},
+        (Bson::Null, false) => doc! {sort: Bson::Null, "_id": {operator: id}},
+        (value, true) => doc! {"$or": [
+            {sort: {operator: value.clone()}},
+            {sort: value, "_id": {operator: id}}
+        ]},
+        (value, false) => doc! {"$or": [
+            {sort: {operator: value.clone()}},
+            {sort: value, "_id": {operator: id}},
+            {sort: Bson::Null}
+        ]},
+    }
+}
+
+impl<M: Model + Send + Sync> Collection<M> {
+    /// Fetches a single page of documents matching `query`, as described by `paginate`
+    pub async fn paginate(
+        &self,
+        query: impl IntoQuery<M>,
+        paginate: Paginate<M>,
+    ) -> MResult<Page<M>> {
+        let query = query.into_query()?;
+        let direction = if paginate.ascending { 1 } else { -1 };
+        let sort = if paginate.sort == "_id" {
+            doc! {"_id": direction}
+        } else {
+            doc! {paginate.sort.clone(): direction, "_id": direction}
+        };
+
+        match paginate.kind {
+            PageKind::Offset(page) => {
+                let total = self.exact_count(query.clone()).await?;
+                let options = FindOptions::builder()
+                    .sort(sort)
+                    .skip(page.saturating_mul(paginate.per_page))
+                    .limit(i64::try_from(paginate.per_page).unwrap_or(i64::MAX))
+                    .build();
+                let items: Vec<M> = self
+                    .find(query, Find::Many(Some(options)))
+                    .await?
+                    .cursor()
+                    .unwrap()
+                    .try_collect()
+                    .await?;
+
+                Ok(Page {
+                    items,
+                    total: Some(total),
+                    next: None,
+                })
+            }
+            PageKind::Keyset(after) => {
+                let filter = match after {
+                    Some(token) => {
+                        let (value, id) = decode_token(&paginate.sort, paginate.ascending, &token)?;
+                        let position = position(&paginate.sort, paginate.ascending, value, id);
+                        if query.is_empty() {
+                            position
+                        } else {
+                            doc! {"$and": [query, position]}
+                        }
+                    }
+                    None => query,
+                };
+
+                // One extra document is fetched to tell whether another page follows
+                let options = FindOptions::builder()
+                    .sort(sort)
+                    .limit(i64::try_from(paginate.per_page.saturating_add(1)).unwrap_or(i64::MAX))
+                    .build();
+                let mut items: Vec<M> = self
+                    .find(filter, Find::Many(Some(options)))
+                    .await?
+                    .cursor()
+                    .unwrap()
+                    .try_collect()
+                    .await?;
+
+                let next = if items.len() as u64 > paginate.per_page {
+                    items.truncate(paginate.per_page as usize);
+                    let last = serialize(items.last().unwrap())?;
+                    Some(encode_token(
+                        &paginate.sort,
+                        paginate.ascending,
+                        lookup(&last, &paginate.sort),
+                        lookup(&last, "_id"),
+                    )?)
+                } else {
+                    None
+                };
+
+                Ok(Page {
+                    items,
+                    total: None,
+                    next,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{Bson, doc};
+
+    use super::{decode_token, encode_token, position};
+
+    #[test]
+    fn token_round_trips() {
+        let token = encode_token("name", true, Bson::from("bob"), Bson::from(3)).unwrap();
+        assert_eq!(decode_token("name", true, &token).unwrap(), (Bson::from("bob"), Bson::from(3)));
+    }
+
+    #[test]
+    fn token_rejects_other_sorts() {
+        let token = encode_token("name", true, Bson::from("bob"), Bson::from(3)).unwrap();
+        assert!(decode_token("age", true, &token).is_err());
+        assert!(decode_token("name", false, &token).is_err());
+    }
+
+    #[test]
+    fn token_rejects_malformed_input() {
+        assert!(decode_token("name", true, "abc").is_err());
+        assert!(decode_token("name", true, "zz").is_err());
+        assert!(decode_token("name", true, "00000000").is_err());
+    }
+
+    #[test]
+    fn position_by_id() {
+        assert_eq!(position("_id", true, Bson::Null, Bson::from(3)), doc! {"_id": {"$gt": 3}});
+        assert_eq!(position("_id", false, Bson::Null, Bson::from(3)), doc! {"_id": {"$lt": 3}});
+    }
+
+    #[test]
+    fn position_after_value() {
+        assert_eq!(
+            position("age", true, Bson::from(30), Bson::from(3)),
+            doc! {"$or": [{"age": {"$gt": 30}}, {"age": 30, "_id": {"$gt": 3}}]}
+        );
+        assert_eq!(
+            position("age", false, Bson::from(30), Bson::from(3)),
+            doc! {"$or": [{"age": {"$lt": 30}}, {"age": 30, "_id": {"$lt": 3}}, {"age": Bson::Null}]}
+        );
+    }
+
+    #[test]
+    fn position_after_null() {
+        assert_eq!(
+            position("age", true, Bson::Null, Bson::from(3)),
+            doc! {"$or": [{"age": Bson::Null, "_id": {"$gt": 3}}, {"age": {"$ne": Bson::Null}}]}
+        );
+        assert_eq!(
+            position("age", false, Bson::Null, Bson::from(3)),
+            doc! {"age": Bson::Null, "_id": {"$lt": 3}}
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use std::future::{Future, IntoFuture};
+use std::pin::Pin;
+
+use bson::{Bson, Document, doc};
+use futures_util::{TryStreamExt, stream};
+
+use crate::{
+    collection::{Collection, Cursor, Find},
+    error::MResult,
+    model::Model,
+    query::{Field, IntoQuery},
+    types::Link,
+};
+
+/// A [Field] holding one or more [Link]s, which can be populated by [FindMany::populate]
+pub trait PopulateField<M> {
+    /// Returns the aggregation stages that fill in the linked documents, using `scratch` as a temporary field name
+    fn stages(&self, scratch: &str) -> Vec<Document>;
+}
+
+/// Builds the `$lookup` & `$set` stages for a link (or array of links) at `path`. Soft-deleted targets are left unresolved.
+fn populate_stages<L: Model + Send + Sync>(path: &str, many: bool, scratch: &str) -> Vec<Document> {
+    let resolve = |link: &str| {
+        doc! {"$mergeObjects": [
+            link,
+            {"_resolved": {"$arrayElemAt": [
+                {"$filter": {
+                    "input": format!("${scratch}"),
+                    "as": "joined",
+                    "cond": {"$eq": ["$$joined._id", format!("{link}.id")]}
+                }},
+                0
+            ]}}
+        ]}
+    };
+
+    // Missing or null links are left as-is
+    let value = if many {
+        doc! {"$cond": [
+            {"$isArray": format!("${path}")},
+            {"$map": {"input": format!("${path}"), "as": "link", "in": resolve("$$link")}},
+            format!("${path}")
+        ]}
+    } else {
+        doc! {"$cond": [
+            {"$eq": [{"$type": format!("${path}")}, "object"]},
+            resolve(&format!("${path}")),
+            format!("${path}")
+        ]}
+    };
+
+    let lookup = match L::soft_delete_field() {
+        // `localField` can't be combined with `pipeline` before MongoDB 5.0, so the IDs are matched with `$expr` instead
+        Some(field) => doc! {
+            "from": L::collection_name(),
+            "let": {"ids": format!("${path}.id")},
+            "pipeline": [{"$match": {
+                field: Bson::Null,
+                "$expr": {"$in": ["$_id", {"$cond": [{"$isArray": "$$ids"}, "$$ids", ["$$ids"]]}]}
+            }}],
+            "as": scratch
+        },
+        None => doc! {
+            "from": L::collection_name(),
+            "localField": format!("{path}.id"),
+            "foreignField": "_id",
+            "as": scratch
+        },
+    };
+
+    vec![
+        doc! {"$lookup": lookup},
+        doc! {"$set": {path: value}},
+        doc! {"$unset": scratch},
+    ]
+}
+
+impl<M, L: Model + Send + Sync> PopulateField<M> for Field<M, Link<L>> {
+    fn stages(&self, scratch: &str) -> Vec<Document> {
+        populate_stages::<L>(self.path(), false, scratch)
+    }
+}
+
+impl<M, L: Model + Send + Sync> PopulateField<M> for Field<M, Option<Link<L>>> {
+    fn stages(&self, scratch: &str) -> Vec<Document> {
+        populate_stages::<L>(self.path(), false, scratch)
+    }
+}
+
+impl<M, L: Model + Send + Sync> PopulateField<M> for Field<M, Vec<Link<L>>> {
+    fn stages(&self, scratch: &str) -> Vec<Document> {
+        populate_stages::<L>(self.path(), true, scratch)
+    }
+}
+
+/// A pending [Collection::find_many] operation. Awaiting it runs the query and returns a [Cursor].
+///
+/// If any [Link] fields are populated (see [FindMany::populate]), the query is run as a single aggregation that joins the linked documents with `$lookup`,
+/// so that [Link::value] returns them without further round trips.
+///
+/// ```no_run
+/// # use manor::{schema, Collection, Link, MResult, bson::Uuid};
+/// # use futures_util::TryStreamExt;
+/// # #[schema(collection = "users")]
+/// # pub struct User {
+/// #     #[field(id = Uuid::new)]
+/// #     pub id: Uuid,
+/// # }
+/// # #[schema(collection = "sessions")]
+/// # pub struct Session {
+/// #     #[field(id = Uuid::new)]
+/// #     pub id: Uuid,
+/// #     pub active: bool,
+/// #     pub user: Link<User>,
+/// # }
+/// # async fn run() -> MResult<()> {
+/// let sessions: Vec<Session> = Collection::<Session>::new()
+///     .find_many(Session::fields.active.eq(true))
+///     .populate(Session::fields.user)
+///     .await?
+///     .try_collect()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct FindMany<M: Model + Send + Sync> {
+    pub(crate) collection: Collection<M>,
+    pub(crate) query: MResult<Document>,
+    pub(crate) populate: Vec<Vec<Document>>,
+}
+
+impl<M: Model + Send + Sync> FindMany<M> {
+    pub(crate) fn new(collection: &Collection<M>, query: impl IntoQuery<M>) -> Self {
+        Self {
+            collection: collection.clone(),
+            query: query.into_query(),
+            populate: Vec::new(),
+        }
+    }
+
+    /// Fills in the linked documents of a [Link], [`Option<Link>`] or [`Vec<Link>`] field in every result
+    pub fn populate(mut self, field: impl PopulateField<M>) -> Self {
+        let scratch = format!("__manor_populate_{}", self.populate.len());
+        self.populate.push(field.stages(&scratch));
+        self
+    }
+
+    async fn run(self) -> MResult<Cursor<M>> {
+        let query = self.query?;
+        if self.populate.is_empty() {
+            return self
+                .collection
+                .find(query, Find::<M>::many())
+                .await
+                .map(|r| r.cursor().unwrap());
+        }
+
+        let pipeline: Vec<Document> = std::iter::once(doc! {"$match": self.collection.scoped(query)})
+            .chain(self.populate.into_iter().flatten())
+            .collect();
+        let collection = self.collection.collection();
+        let action = collection.aggregate(pipeline).with_type::<M>();
+
+        if let Some(session) = &self.collection.session {
+            // As with find, results are buffered while the session is held
+            let mut guard = session.lock().await;
+            let mut cursor = action.session(&mut *guard).await?;
+            let records: Vec<M> = cursor.stream(&mut guard).try_collect().await?;
+            Ok(self
+                .collection
+                .stream_cursor(stream::iter(records.into_iter().map(Ok))))
+        } else {
+            Ok(self.collection.cursor(action.await?))
+        }
+    }
+}
+
+impl<M: Model + Send + Sync> IntoFuture for FindMany<M> {
+    type Output = MResult<Cursor<M>>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Self::Output> + Send>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.run())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+
+    use super::populate_stages;
+    use crate::testing::Record;
+
+    #[test]
+    fn populates_links() {
+        let stages = populate_stages::<Record>("owner", false, "scratch");
+        assert_eq!(
+            stages[0],
+            doc! {"$lookup": {
+                "from": "records",
+                "let": {"ids": "$owner.id"},
+                "pipeline": [{"$match": {
+                    "deleted_at": null,
+                    "$expr": {"$in": ["$_id", {"$cond": [{"$isArray": "$$ids"}, "$$ids", ["$$ids"]]}]}
+                }}],
+                "as": "scratch"
+            }}
+        );
+        assert_eq!(
+            stages[1],
+            doc! {"$set": {"owner": {"$cond": [
+                {"$eq": [{"$type": "$owner"}, "object"]},
+                {"$mergeObjects": [
+                    "$owner",
+                    {"_resolved": {"$arrayElemAt": [
+                        {"$filter": {"input": "$scratch", "as": "joined", "cond": {"$eq": ["$$joined._id", "$owner.id"]}}},
+                        0
+                    ]}}
+                ]},
+                "$owner"
+            ]}}}
+        );
+        assert_eq!(stages[2], doc! {"$unset": "scratch"});
+    }
+
+    #[test]
+    fn populates_link_arrays() {
+        let stages = populate_stages::<Record>("owners", true, "scratch");
+        assert_eq!(stages.len(), 3);
+        assert_eq!(
+            stages[1],
+            doc! {"$set": {"owners": {"$cond": [
+                {"$isArray": "$owners"},
+                {"$map": {"input": "$owners", "as": "link", "in": {"$mergeObjects": [
+                    "$$link",
+                    {"_resolved": {"$arrayElemAt": [
+                        {"$filter": {"input": "$scratch", "as": "joined", "cond": {"$eq": ["$$joined._id", "$$link.id"]}}},
+                        0
+                    ]}}
+                ]}}},
+                "$owners"
+            ]}}}
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use bson::{Document, doc};
+use futures_util::{TryStreamExt, stream::{self, BoxStream}};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    collection::{Collection, with_session},
+    error::{Error, MResult},
+    model::Model,
+    query::IntoQuery,
+};
+
+/// A partial view of a model `M`, loaded by [Collection::find_many_as] & [Collection::find_one_as] with only the listed fields.
+/// Generally generated with `#[schema(projection(Name: field, ...))]`, which checks the fields against the model at compile time.
+pub trait Projection<M: Model + Send + Sync>: DeserializeOwned + Send + Sync + Unpin + 'static {
+    /// Returns the serialized paths of the projected fields
+    fn fields() -> Vec<&'static str>;
+
+    /// Returns the projection document for [Projection::fields]. `_id` is excluded unless listed.
+    fn projection() -> Document {
+        let mut projection = doc! {"_id": 0};
+        for field in Self::fields() {
+            projection.insert(field, 1);
+        }
+        projection
+    }
+}
+
+impl<M: Model + Send + Sync> Collection<M> {
+    /// Finds many documents, loading only the fields of the projection `P`. Soft-deleted documents are excluded, as with [Collection::find_many].
+    ///
+    /// ```no_run
+    /// # use manor::{schema, Collection, MResult, bson::Uuid};
+    /// # use futures_util::TryStreamExt;
+    /// # #[schema(collection = "users", projection(UserSummary: id, username))]
+    /// # pub struct User {
+    /// #     #[field(id = Uuid::new)]
+    /// #     pub id: Uuid,
+    /// #     pub username: String,
+    /// #     pub active: bool,
+    /// # }
+    /// # async fn run(users: Collection<User>) -> MResult<()> {
+    /// let summaries: Vec<UserSummary> = users.find_many_as::<UserSummary>(User::fields.active.eq(true)).await?.try_collect().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn find_many_as<P: Projection<M>>(
+        &self,
+        query: impl IntoQuery<M>,
+    ) -> MResult<BoxStream<'static, MResult<P>>> {
+        let collection = self.collection().clone_with_type::<P>();
+        let action = collection
+            .find(self.scoped(query.into_query()?))
+            .projection(P::projection());
+        if let Some(session) = &self.session {
+            // As with find, results are buffered while the session is held
+            let mut guard = session.lock().await;
+            let mut cursor = action.session(&mut *guard).await?;
+            let records: Vec<P> = cursor.stream(&mut guard).try_collect().await?;
+            Ok(Box::pin(stream::iter(records.into_iter().map(Ok))))
+        } else {
+            Ok(Box::pin(action.await?.map_err(Error::from)))
+        }
+    }
+
+    /// Finds at most one document, loading only the fields of the projection `P`
+    pub async fn find_one_as<P: Projection<M>>(&self, query: impl IntoQuery<M>) -> MResult<Option<P>> {
+        let collection = self.collection().clone_with_type::<P>();
+        let action = collection
+            .find_one(self.scoped(query.into_query()?))
+            .projection(P::projection());
+        with_session!(self, action).map_err(Error::from)
+    }
+}
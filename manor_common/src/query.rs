@@ -0,0 +1,370 @@
+use std::{borrow::Cow, marker::PhantomData};
+
+use bson::{Bson, Document, doc};
+use serde::Serialize;
+
+use crate::{
+    collection::serialize_value,
+    error::{Error, MResult},
+    model::Model,
+    types::Link,
+};
+
+/// A typed reference to a (possibly nested) field of a [Model], generated by `#[schema(...)]` as `Model::fields.<name>`.
+///
+/// `M` is the model the field belongs to, and `T` is the Rust type of the field. Any [Query] built from a [Field] can only be used with a [crate::collection::Collection] of the same model,
+/// and comparison values must convert into the field's type.
+pub struct Field<M, T> {
+    path: Cow<'static, str>,
+    _marker: PhantomData<fn() -> (M, T)>,
+}
+
+impl<M, T> Clone for Field<M, T> {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<M, T> std::fmt::Debug for Field<M, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Field").field(&self.path).finish()
+    }
+}
+
+impl<M, T> Field<M, T> {
+    /// Creates a field descriptor from its serialized path. Generally only used by generated code.
+    pub const fn new(path: &'static str) -> Self {
+        Self {
+            path: Cow::Borrowed(path),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the serialized (dotted) path of this field
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns a descriptor for a nested field of this field, of type `U`
+    pub fn child<U>(&self, name: impl AsRef<str>) -> Field<M, U> {
+        Field {
+            path: Cow::Owned(format!("{}.{}", self.path, name.as_ref())),
+            _marker: PhantomData,
+        }
+    }
+
+    fn compare(&self, operator: &str, value: Result<Bson, bson::ser::Error>) -> Query<M> {
+        Query::from_result(value.map(|v| doc! {self.path(): {operator: v}}))
+    }
+
+    fn compare_many<V: Into<T>>(&self, operator: &str, values: impl IntoIterator<Item = V>) -> Query<M>
+    where
+        T: Serialize,
+    {
+        let values = values
+            .into_iter()
+            .map(|v| serialize_value(&v.into()))
+            .collect::<Result<Vec<Bson>, _>>();
+        self.compare(operator, values.map(Bson::Array))
+    }
+
+    /// Matches documents where this field exists (or does not exist)
+    pub fn exists(&self, exists: bool) -> Query<M> {
+        self.compare("$exists", Ok(Bson::Boolean(exists)))
+    }
+}
+
+impl<M, T: Serialize> Field<M, T> {
+    /// Matches documents where this field equals `value`
+    pub fn eq(&self, value: impl Into<T>) -> Query<M> {
+        Query::from_result(serialize_value(&value.into()).map(|v| doc! {self.path(): v}))
+    }
+
+    /// Matches documents where this field does not equal `value`
+    pub fn ne(&self, value: impl Into<T>) -> Query<M> {
+        self.compare("$ne", serialize_value(&value.into()))
+    }
+
+    /// Matches documents where this field is greater than `value`
+    pub fn gt(&self, value: impl Into<T>) -> Query<M> {
+        self.compare("$gt", serialize_value(&value.into()))
+    }
+
+    /// Matches documents where this field is greater than or equal to `value`
+    pub fn gte(&self, value: impl Into<T>) -> Query<M> {
+        self.compare("$gte", serialize_value(&value.into()))
+    }
+
+    /// Matches documents where this field is less than `value`
+    pub fn lt(&self, value: impl Into<T>) -> Query<M> {
+        self.compare("$lt", serialize_value(&value.into()))
+    }
+
+    /// Matches documents where this field is less than or equal to `value`
+    pub fn lte(&self, value: impl Into<T>) -> Query<M> {
+        self.compare("$lte", serialize_value(&value.into()))
+    }
+
+    /// Matches documents where this field equals any of `values`
+    pub fn in_<V: Into<T>>(&self, values: impl IntoIterator<Item = V>) -> Query<M> {
+        self.compare_many("$in", values)
+    }
+
+    /// Matches documents where this field equals none of `values`
+    pub fn nin<V: Into<T>>(&self, values: impl IntoIterator<Item = V>) -> Query<M> {
+        self.compare_many("$nin", values)
+    }
+}
+
+impl<M> Field<M, String> {
+    /// Matches documents where this field matches a regular expression, with optional [regex options](https://www.mongodb.com/docs/manual/reference/operator/query/regex/#mongodb-query-op.-options)
+    pub fn regex(&self, pattern: impl Into<String>, options: impl Into<String>) -> Query<M> {
+        self.compare(
+            "$regex",
+            Ok(Bson::RegularExpression(bson::Regex {
+                pattern: pattern.into(),
+                options: options.into(),
+            })),
+        )
+    }
+}
+
+impl<M, T: Serialize> Field<M, Vec<T>> {
+    /// Matches documents where this array field contains `value`
+    pub fn contains(&self, value: impl Into<T>) -> Query<M> {
+        Query::from_result(serialize_value(&value.into()).map(|v| doc! {self.path(): v}))
+    }
+
+    /// Matches documents where this array field contains all of `values`
+    pub fn contains_all<V: Into<T>>(&self, values: impl IntoIterator<Item = V>) -> Query<M> {
+        let values = values
+            .into_iter()
+            .map(|v| serialize_value(&v.into()))
+            .collect::<Result<Vec<Bson>, _>>();
+        self.compare("$all", values.map(Bson::Array))
+    }
+
+    /// Matches documents where this array field has exactly `size` elements
+    pub fn size(&self, size: u32) -> Query<M> {
+        self.compare("$size", Ok(Bson::Int64(size as i64)))
+    }
+}
+
+impl<M, L: Model + Send + Sync> Field<M, Link<L>> {
+    /// Returns the ID field of the linked document reference
+    pub fn id(&self) -> Field<M, L::Id> {
+        self.child("id")
+    }
+}
+
+impl<M, L: Model + Send + Sync> Field<M, Option<Link<L>>> {
+    /// Returns the ID field of the linked document reference
+    pub fn id(&self) -> Field<M, L::Id> {
+        self.child("id")
+    }
+}
+
+impl<M, L: Model + Send + Sync> Field<M, Vec<Link<L>>> {
+    /// Returns the ID field of the linked document references. Queries on this field match if any element matches.
+    pub fn id(&self) -> Field<M, L::Id> {
+        self.child("id")
+    }
+}
+
+/// A typed query (filter) against documents of a [Model], built from [Field] descriptors.
+///
+/// Serialization errors in comparison values are deferred until the query is used.
+pub struct Query<M> {
+    document: Result<Document, bson::ser::Error>,
+    _marker: PhantomData<fn() -> M>,
+}
+
+impl<M> Clone for Query<M> {
+    fn clone(&self) -> Self {
+        Self {
+            document: self.document.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<M> std::fmt::Debug for Query<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Query").field(&self.document).finish()
+    }
+}
+
+impl<M> Default for Query<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> Query<M> {
+    fn from_result(document: Result<Document, bson::ser::Error>) -> Self {
+        Self {
+            document,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates an empty query, matching every document
+    pub fn new() -> Self {
+        Self::raw(Document::new())
+    }
+
+    /// Creates a query from a raw [Document], bypassing type checking
+    pub fn raw(document: Document) -> Self {
+        Self::from_result(Ok(document))
+    }
+
+    fn combine(operator: &str, queries: impl IntoIterator<Item = Query<M>>) -> Self {
+        let documents = queries
+            .into_iter()
+            .map(|q| q.document)
+            .collect::<Result<Vec<Document>, _>>();
+        Self::from_result(documents.map(|mut docs| match operator {
+            // Empty operands match every document: they can be dropped from a conjunction, and make a disjunction match everything
+            "$and" => {
+                docs.retain(|d| !d.is_empty());
+                match docs.len() {
+                    0 => Document::new(),
+                    1 => docs.remove(0),
+                    _ => doc! {operator: docs},
+                }
+            }
+            _ if docs.iter().any(Document::is_empty) => Document::new(),
+            _ => match docs.len() {
+                0 => doc! {"$nor": [{}]},
+                1 => docs.remove(0),
+                _ => doc! {operator: docs},
+            },
+        }))
+    }
+
+    /// Matches documents that match every query. An empty set of queries matches every document.
+    pub fn all(queries: impl IntoIterator<Item = Query<M>>) -> Self {
+        Self::combine("$and", queries)
+    }
+
+    /// Matches documents that match at least one query. An empty set of queries matches no documents.
+    pub fn any(queries: impl IntoIterator<Item = Query<M>>) -> Self {
+        Self::combine("$or", queries)
+    }
+
+    /// Matches documents that match both this query and `other`
+    pub fn and(self, other: Query<M>) -> Self {
+        Self::all([self, other])
+    }
+
+    /// Matches documents that match either this query or `other`
+    pub fn or(self, other: Query<M>) -> Self {
+        Self::any([self, other])
+    }
+
+    /// Returns the compiled query document
+    pub fn document(&self) -> MResult<Document> {
+        self.document.clone().map_err(Error::from)
+    }
+}
+
+/// Negates a query, matching documents that do not match it
+impl<M> std::ops::Not for Query<M> {
+    type Output = Query<M>;
+
+    fn not(self) -> Self::Output {
+        Self::from_result(self.document.map(|d| doc! {"$nor": [d]}))
+    }
+}
+
+/// A trait for values that can be used as a query against a [Model]'s collection. Implemented for raw [Document]s and typed [Query]s.
+pub trait IntoQuery<M: Model + Send + Sync> {
+    /// Converts this value into a query document
+    fn into_query(self) -> MResult<Document>;
+}
+
+impl<M: Model + Send + Sync> IntoQuery<M> for Document {
+    fn into_query(self) -> MResult<Document> {
+        Ok(self)
+    }
+}
+
+impl<M: Model + Send + Sync> IntoQuery<M> for Query<M> {
+    fn into_query(self) -> MResult<Document> {
+        self.document.map_err(Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{Bson, Document, doc};
+    use uuid::Uuid;
+
+    use super::{Field, Query};
+
+    fn query(document: Document) -> Query<()> {
+        Query::raw(document)
+    }
+
+    #[test]
+    fn and_drops_empty_operands() {
+        let combined = query(doc! {}).and(query(doc! {"a": 1}));
+        assert_eq!(combined.document().unwrap(), doc! {"a": 1});
+
+        let combined = Query::all([query(doc! {"a": 1}), query(doc! {}), query(doc! {"b": 2})]);
+        assert_eq!(combined.document().unwrap(), doc! {"$and": [{"a": 1}, {"b": 2}]});
+    }
+
+    #[test]
+    fn and_of_nothing_matches_everything() {
+        assert_eq!(Query::<()>::all([]).document().unwrap(), doc! {});
+        assert_eq!(Query::all([query(doc! {}), query(doc! {})]).document().unwrap(), doc! {});
+    }
+
+    #[test]
+    fn or_with_empty_operand_matches_everything() {
+        let combined = Query::new().or(query(doc! {"a": 1}));
+        assert_eq!(combined.document().unwrap(), doc! {});
+    }
+
+    #[test]
+    fn or_of_nothing_matches_nothing() {
+        assert_eq!(Query::<()>::any([]).document().unwrap(), doc! {"$nor": [{}]});
+    }
+
+    #[test]
+    fn or_combines_operands() {
+        assert_eq!(Query::any([query(doc! {"a": 1})]).document().unwrap(), doc! {"a": 1});
+
+        let combined = query(doc! {"a": 1}).or(query(doc! {"b": 2}));
+        assert_eq!(combined.document().unwrap(), doc! {"$or": [{"a": 1}, {"b": 2}]});
+    }
+
+    #[test]
+    fn not_wraps_in_nor() {
+        assert_eq!((!query(doc! {"a": 1})).document().unwrap(), doc! {"$nor": [{"a": 1}]});
+    }
+
+    #[test]
+    fn compares_uuids_as_binary() {
+        let field = Field::<(), Uuid>::new("_id");
+        let id = Uuid::new_v4();
+        let document = field.eq(id).document().unwrap();
+        assert!(matches!(document.get("_id"), Some(Bson::Binary(_))));
+
+        let document = field.in_([id]).document().unwrap();
+        let values = document.get_document("_id").unwrap().get_array("$in").unwrap();
+        assert!(matches!(values.as_slice(), [Bson::Binary(_)]));
+    }
+
+    #[test]
+    fn compares_values() {
+        let field = Field::<(), i64>::new("age");
+        assert_eq!(field.eq(3).document().unwrap(), doc! {"age": 3_i64});
+        assert_eq!(field.gte(3).document().unwrap(), doc! {"age": {"$gte": 3_i64}});
+        assert_eq!(field.nin([1, 2]).document().unwrap(), doc! {"age": {"$nin": [1_i64, 2_i64]}});
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures_util::lock::{Mutex, MutexGuard};
+use mongodb::{
+    ClientSession,
+    error::{TRANSIENT_TRANSACTION_ERROR, UNKNOWN_TRANSACTION_COMMIT_RESULT},
+};
+
+use crate::{
+    client::Client,
+    collection::Collection,
+    error::{Error, MResult},
+    gridfs::FileLink,
+    integrity::delete_files,
+    model::Model,
+};
+
+/// How long [Client::transaction] keeps retrying transient failures before giving up, matching the MongoDB driver's convenient transaction API
+const TRANSACTION_RETRY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A shareable handle to a [mongodb::ClientSession]. Collections bound to a session (see [Session::collection] and [Collection::with_session]) run all of their operations within it.
+///
+/// Session cursors need exclusive access to the session for every batch they fetch, so multi-document finds on a session-bound collection
+/// (`find_many`, `find_many_as`, paginated finds and populated finds) read every result into memory before returning. Use `limit`s or pagination for large result sets.
+#[derive(Clone)]
+pub struct Session {
+    pub(crate) client: Client,
+    pub(crate) session: Arc<Mutex<ClientSession>>,
+    pub(crate) files: Arc<Mutex<Vec<FileLink>>>,
+}
+
+impl std::fmt::Debug for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Session")
+            .field("client", &self.client)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Session {
+    /// Returns this session's [Client]
+    pub fn client(&self) -> Client {
+        self.client.clone()
+    }
+
+    /// Returns a typed [Collection] whose operations run within this session
+    pub fn collection<M: Model + Send + Sync>(&self) -> Collection<M> {
+        self.client.collection::<M>().with_session(self)
+    }
+
+    /// Locks and returns the underlying [ClientSession]. Operations on session-bound collections will wait until the guard is dropped.
+    pub async fn lock(&self) -> MutexGuard<'_, ClientSession> {
+        self.session.lock().await
+    }
+
+    /// Deletes the files unlinked by deletions within this session (see `#[field(delete_file)]`), which GridFS cannot delete within a transaction.
+    /// [Client::transaction] calls this once the transaction is committed, and sessions committed manually should call it afterwards.
+    pub async fn delete_files(&self) -> MResult<()> {
+        let files = std::mem::take(&mut *self.files.lock().await);
+        delete_files(&self.client, files).await
+    }
+
+    /// Forgets the files unlinked by deletions within this session, ie after aborting its transaction
+    pub async fn discard_files(&self) {
+        self.files.lock().await.clear();
+    }
+}
+
+impl Client {
+    /// Starts a new [Session]
+    pub async fn start_session(&self) -> MResult<Session> {
+        Ok(Session {
+            client: self.clone(),
+            session: Arc::new(Mutex::new(self.client.start_session().await?)),
+            files: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    /// Runs `operation` within a multi-document transaction, committing if it returns [Ok] and aborting otherwise.
+    ///
+    /// The closure receives a [Session], which should be used to obtain the collections (or [Model] helpers, see [Model::save_with_session]) involved in the transaction.
+    /// If the transaction fails with a `TransientTransactionError` the whole closure is retried, and if the commit fails with `UnknownTransactionCommitResult` the commit is retried,
+    /// for up to 120 seconds.
+    ///
+    /// Files unlinked by deletions within the transaction are deleted once it is committed (see [Session::delete_files]). Failing to delete them does not fail the transaction,
+    /// as its changes are already committed: the error is logged with [tracing] instead, and the files are left in their bucket.
+    ///
+    /// ```no_run
+    /// # use manor::{schema, Client, MResult, bson::Uuid};
+    /// # #[schema(collection = "users")]
+    /// # pub struct User {
+    /// #     #[field(id = Uuid::new)]
+    /// #     pub id: Uuid,
+    /// # }
+    /// # async fn run(client: Client, alice: User, bob: User) -> MResult<()> {
+    /// client.transaction(|txn| {
+    ///     let (alice, bob) = (alice.clone(), bob.clone());
+    ///     async move {
+    ///         let users = txn.collection::<User>();
+    ///         users.save(alice).await?;
+    ///         users.delete(bob).await?;
+    ///         Ok(())
+    ///     }
+    /// }).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn transaction<T, F, Fut>(&self, mut operation: F) -> MResult<T>
+    where
+        F: FnMut(Session) -> Fut,
+        Fut: Future<Output = MResult<T>>,
+    {
+        let session = self.start_session().await?;
+        let started = Instant::now();
+
+        'transaction: loop {
+            session.lock().await.start_transaction().await?;
+            session.discard_files().await;
+
+            let value = match operation(session.clone()).await {
+                Ok(value) => value,
+                Err(error) => {
+                    let _ = session.lock().await.abort_transaction().await;
+                    if error.has_label(TRANSIENT_TRANSACTION_ERROR)
+                        && started.elapsed() < TRANSACTION_RETRY_TIMEOUT
+                    {
+                        continue 'transaction;
+                    }
+                    return Err(error);
+                }
+            };
+
+            loop {
+                let result = session.lock().await.commit_transaction().await;
+                match result.map_err(Error::from) {
+                    Ok(()) => {
+                        // The work is committed, so a failed cleanup must not look like a failed (and retryable) transaction
+                        if let Err(error) = session.delete_files().await {
+                            tracing::warn!(%error, "Failed to delete files unlinked by a committed transaction");
+                        }
+                        return Ok(value);
+                    }
+                    Err(error) if started.elapsed() >= TRANSACTION_RETRY_TIMEOUT => {
+                        return Err(error);
+                    }
+                    Err(error) if error.has_label(UNKNOWN_TRANSACTION_COMMIT_RESULT) => continue,
+                    Err(error) if error.has_label(TRANSIENT_TRANSACTION_ERROR) => {
+                        continue 'transaction;
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use bson::{Bson, Document, doc};
+use mongodb::options::{DeleteOptions, FindOneAndDeleteOptions, FindOneAndUpdateOptions, UpdateOptions};
+
+use crate::{
+    collection::{Collection, Ops},
+    error::MResult,
+    model::Model,
+    query::IntoQuery,
+};
+
+/// Converts delete options to the equivalent update options, for soft deletes
+fn delete_to_update_options(options: Option<DeleteOptions>) -> Option<UpdateOptions> {
+    options.map(|o| {
+        UpdateOptions::builder()
+            .collation(o.collation)
+            .hint(o.hint)
+            .write_concern(o.write_concern)
+            .let_vars(o.let_vars)
+            .comment(o.comment)
+            .build()
+    })
+}
+
+/// Converts find/delete options to the equivalent find/update options, for soft deletes
+pub(crate) fn find_delete_to_update_options(options: Option<FindOneAndDeleteOptions>) -> Option<FindOneAndUpdateOptions> {
+    options.map(|o| {
+        FindOneAndUpdateOptions::builder()
+            .max_time(o.max_time)
+            .projection(o.projection)
+            .sort(o.sort)
+            .write_concern(o.write_concern)
+            .collation(o.collation)
+            .hint(o.hint)
+            .let_vars(o.let_vars)
+            .comment(o.comment)
+            .build()
+    })
+}
+
+/// Returns the update that marks documents of a `#[schema(soft_delete)]` model as deleted, if `M` is one
+pub(crate) fn deletion<M: Model + Send + Sync>() -> Option<Document> {
+    M::soft_delete_field().map(|field| doc! {"$set": {field: bson::DateTime::now()}})
+}
+
+/// Restricts a query to documents that have not been soft-deleted
+pub(crate) fn exclude_deleted<M: Model + Send + Sync>(mut query: Document) -> Document {
+    if let Some(field) = M::soft_delete_field()
+        && !query.contains_key(field)
+    {
+        query.insert(field, Bson::Null);
+    }
+    query
+}
+
+impl<M: Model + Send + Sync> Collection<M> {
+    /// Returns a copy of this collection whose finds, gets & counts include soft-deleted documents (see `#[schema(soft_delete)]`)
+    pub fn with_deleted(&self) -> Self {
+        Self {
+            include_deleted: true,
+            ..self.clone()
+        }
+    }
+
+    /// Restricts a query to documents that have not been soft-deleted, unless this collection was created with [Collection::with_deleted]
+    pub(crate) fn scoped(&self, query: Document) -> Document {
+        if self.include_deleted {
+            query
+        } else {
+            exclude_deleted::<M>(query)
+        }
+    }
+
+    /// Marks matching documents as deleted instead of removing them. Documents that are already deleted are left as-is.
+    pub(crate) async fn soft_delete(
+        &self,
+        query: Document,
+        update: Document,
+        operations: Ops,
+        options: Option<DeleteOptions>,
+    ) -> MResult<u64> {
+        self.update_with_options(exclude_deleted::<M>(query), update, operations, delete_to_update_options(options))
+            .await
+            .map(|r| r.modified_count)
+    }
+
+    /// Restores soft-deleted documents matching `query`, returning the number of restored documents. Does nothing for models without `#[schema(soft_delete)]`.
+    pub async fn restore(&self, query: impl IntoQuery<M>) -> MResult<u64> {
+        let Some(field) = M::soft_delete_field() else {
+            return Ok(0);
+        };
+
+        let mut query = query.into_query()?;
+        query.insert(field, doc! {"$ne": Bson::Null});
+        self.update_many(query, doc! {"$set": {field: Bson::Null}})
+            .await
+            .map(|r| r.modified_count)
+    }
+
+    /// Permanently deletes every document matching `query`, whether soft-deleted or not, enforcing any `on_delete` policies of links to this model
+    pub async fn purge(&self, query: impl IntoQuery<M>) -> MResult<u64> {
+        self.delete_enforced(query.into_query()?, Ops::Many, None)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{Bson, doc};
+
+    use super::{deletion, exclude_deleted};
+    use crate::testing::Record;
+
+    #[test]
+    fn excludes_deleted_documents() {
+        assert_eq!(exclude_deleted::<Record>(doc! {}), doc! {"deleted_at": null});
+        assert_eq!(exclude_deleted::<Record>(doc! {"_id": 1}), doc! {"_id": 1, "deleted_at": null});
+    }
+
+    #[test]
+    fn keeps_deleted_at_constraints() {
+        let query = doc! {"deleted_at": {"$ne": null}};
+        assert_eq!(exclude_deleted::<Record>(query.clone()), query);
+        let query = doc! {"_id": 1, "deleted_at": {"$lt": bson::DateTime::MIN}};
+        assert_eq!(exclude_deleted::<Record>(query.clone()), query);
+    }
+
+    #[test]
+    fn marks_documents_deleted() {
+        let update = deletion::<Record>().unwrap();
+        assert!(matches!(update.get_document("$set").unwrap().get("deleted_at"), Some(Bson::DateTime(_))));
+    }
+}
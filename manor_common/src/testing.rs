@@ -0,0 +1,115 @@
+use bson::{DateTime, Document, doc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    collection::Collection,
+    error::{Error, MResult},
+    hooks::Hooks,
+    model::Model,
+    validate::ValidationError,
+};
+
+/// The time returned by [Record]'s timestamps
+pub(crate) const NOW: DateTime = DateTime::from_millis(1_700_000_000_000);
+
+/// A model with timestamps, a version and soft deletion, stored in the `records` collection
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct Record {
+    #[serde(rename = "_id")]
+    pub(crate) id: i64,
+    pub(crate) created: Option<DateTime>,
+    pub(crate) updated: DateTime,
+    pub(crate) version: i64,
+}
+
+impl Record {
+    pub(crate) fn new(id: i64) -> Self {
+        Record { id, created: None, updated: NOW, version: 0 }
+    }
+}
+
+impl Hooks for Record {}
+
+impl Model for Record {
+    type Id = i64;
+
+    fn from_document(document: Document, _: Option<Collection<Self>>) -> MResult<Self> {
+        Ok(bson::from_document(document)?)
+    }
+
+    fn collection_name() -> String {
+        String::from("records")
+    }
+
+    fn own_collection(&self) -> Option<Collection<Self>> {
+        None
+    }
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn generate_id() -> i64 {
+        0
+    }
+
+    fn attach_collection(&mut self, _: Collection<Self>) {}
+
+    fn validate(&self) -> MResult<()> {
+        match self.id < 0 {
+            true => Err(Error::Validation(vec![ValidationError::new("_id", "must be at least 0")])),
+            false => Ok(()),
+        }
+    }
+
+    fn timestamp_fields() -> (Option<&'static str>, Option<&'static str>) {
+        (Some("created"), Some("updated"))
+    }
+
+    fn timestamps() -> MResult<Document> {
+        Ok(doc! {"created": NOW, "updated": NOW})
+    }
+
+    fn version_field() -> Option<&'static str> {
+        Some("version")
+    }
+
+    fn soft_delete_field() -> Option<&'static str> {
+        Some("deleted_at")
+    }
+}
+
+/// A model keyed by [uuid::Uuid], stored in the `accounts` collection
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct Account {
+    #[serde(rename = "_id")]
+    pub(crate) id: uuid::Uuid,
+}
+
+impl Hooks for Account {}
+
+impl Model for Account {
+    type Id = uuid::Uuid;
+
+    fn from_document(document: Document, _: Option<Collection<Self>>) -> MResult<Self> {
+        Ok(bson::from_document(document)?)
+    }
+
+    fn collection_name() -> String {
+        String::from("accounts")
+    }
+
+    fn own_collection(&self) -> Option<Collection<Self>> {
+        None
+    }
+
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn generate_id() -> uuid::Uuid {
+        uuid::Uuid::new_v4()
+    }
+
+    fn attach_collection(&mut self, _: Collection<Self>) {}
+}
@@ -0,0 +1,360 @@
+use bson::{Bson, Document, doc};
+use mongodb::options::{FindOneAndReplaceOptions, FindOneAndUpdateOptions, ReplaceOptions, UpdateModifications, UpdateOptions};
+
+use crate::{collection::serialize, error::MResult, model::Model};
+
+/// Types that can be used for `#[field(created_at)]` and `#[field(updated_at)]` fields
+pub trait Timestamp {
+    /// Returns the current time
+    fn now() -> Self;
+
+    /// Returns `false` if this timestamp has not been set yet (ie an optional timestamp that is [None]), so that inserts should set `created_at`
+    fn is_set(&self) -> bool {
+        true
+    }
+}
+
+impl Timestamp for chrono::DateTime<chrono::Utc> {
+    fn now() -> Self {
+        chrono::Utc::now()
+    }
+}
+
+impl Timestamp for bson::DateTime {
+    fn now() -> Self {
+        bson::DateTime::now()
+    }
+}
+
+impl<T: Timestamp> Timestamp for Option<T> {
+    fn now() -> Self {
+        Some(T::now())
+    }
+
+    fn is_set(&self) -> bool {
+        self.is_some()
+    }
+}
+
+/// A replacement for a stored document, with timestamps applied
+pub(crate) enum Replacement {
+    /// The stamped document itself
+    Document,
+
+    /// An update pipeline that replaces the document while preserving some of its stored fields (`_id` and/or `created_at`)
+    Pipeline(Vec<Document>),
+}
+
+/// Returns `true` if any operator in an update document already sets `path`
+pub(crate) fn touches(update: &Document, path: &str) -> bool {
+    update
+        .values()
+        .filter_map(Bson::as_document)
+        .any(|fields| fields.contains_key(path))
+}
+
+/// Sets a document's timestamps before it is inserted. A `created_at` value set by the caller is kept.
+pub(crate) fn stamp_insert<M: Model + Send + Sync>(mut document: M) -> M {
+    document.touch(true);
+    document
+}
+
+/// Sets a document's `updated_at` timestamp before it replaces a stored document. If the model has a `created_at` field, or `keep_id` is set (the query may match
+/// a document with another ID), the replacement is performed as a pipeline that keeps the stored values (so change streams report it as an update, see [crate::watch::ChangeEvent::Replaced]).
+/// When upserting, they are taken from the replacement instead
+/// (or set to the current time, for a `created_at` field that is unset).
+pub(crate) fn stamp_replace<M: Model + Send + Sync>(document: &mut M, keep_id: bool) -> MResult<Replacement> {
+    document.touch(false);
+    let created = M::timestamp_fields().0;
+    if created.is_none() && !keep_id {
+        return Ok(Replacement::Document);
+    }
+
+    let mut replacement = serialize(&*document)?;
+    let mut merged = Vec::new();
+    if keep_id {
+        let id = replacement.remove("_id").unwrap_or(Bson::Null);
+        merged.push(Bson::from(doc! {"_id": {"$ifNull": ["$_id", {"$literal": id}]}}));
+    }
+    let created = match created {
+        Some(created) => {
+            let now = match replacement.get(created) {
+                Some(Bson::Null) | None => M::timestamps()?.get(created).cloned().unwrap_or(Bson::Null),
+                Some(value) => value.clone(),
+            };
+            Some(doc! {created: {"$ifNull": [format!("${created}"), {"$literal": now}]}})
+        }
+        None => None,
+    };
+    merged.push(Bson::from(doc! {"$literal": replacement}));
+    merged.extend(created.map(Bson::from));
+    Ok(Replacement::Pipeline(vec![doc! {"$replaceWith": {"$mergeObjects": merged}}]))
+}
+
+/// Restricts the filter of a by-ID replacement to a stored document with the replacement's `created_at`, so that it can be replaced as is (keeping the stored value
+/// without a pipeline). Returns [None] if the model has no `created_at` field, or the replacement's is unset.
+pub(crate) fn created_filter<M: Model + Send + Sync>(filter: &Document, document: &M) -> MResult<Option<Document>> {
+    let Some(path) = M::timestamp_fields().0 else {
+        return Ok(None);
+    };
+    Ok(match serialize(document)?.remove(path) {
+        Some(Bson::Null) | None => None,
+        Some(created) => {
+            let mut filter = filter.clone();
+            filter.insert(path, created);
+            Some(filter)
+        }
+    })
+}
+
+/// Adds the model's timestamps to an update: `updated_at` is always set, and `created_at` is set when upserting a new document.
+/// Fields already set by the update are left as-is.
+pub(crate) fn stamp_update<M: Model + Send + Sync>(update: UpdateModifications) -> MResult<UpdateModifications> {
+    let (created, updated) = M::timestamp_fields();
+    if created.is_none() && updated.is_none() {
+        return Ok(update);
+    }
+
+    let now = M::timestamps()?;
+    let value = |path: &str| now.get(path).cloned().unwrap_or(Bson::Null);
+    Ok(match update {
+        UpdateModifications::Document(mut update) => {
+            for (operator, path) in [("$set", updated), ("$setOnInsert", created)] {
+                if let Some(path) = path.filter(|p| !touches(&update, p)) {
+                    let fields = update
+                        .entry(operator.to_string())
+                        .or_insert_with(|| Bson::Document(Document::new()));
+                    if let Bson::Document(fields) = fields {
+                        fields.insert(path, value(path));
+                    }
+                }
+            }
+            UpdateModifications::Document(update)
+        }
+        UpdateModifications::Pipeline(mut pipeline) => {
+            let mut stage = Document::new();
+            if let Some(path) = updated {
+                stage.insert(path, doc! {"$literal": value(path)});
+            }
+            if let Some(path) = created {
+                stage.insert(path, doc! {"$ifNull": [format!("${path}"), {"$literal": value(path)}]});
+            }
+            pipeline.push(doc! {"$set": stage});
+            UpdateModifications::Pipeline(pipeline)
+        }
+        other => other,
+    })
+}
+
+/// Converts replace options to the equivalent find/update options, for pipeline replacements that return the replaced document's ID
+pub(crate) fn find_replace_options(options: Option<ReplaceOptions>) -> Option<FindOneAndUpdateOptions> {
+    options.map(|o| {
+        FindOneAndUpdateOptions::builder()
+            .bypass_document_validation(o.bypass_document_validation)
+            .upsert(o.upsert)
+            .collation(o.collation)
+            .hint(o.hint)
+            .write_concern(o.write_concern)
+            .let_vars(o.let_vars)
+            .comment(o.comment)
+            .sort(o.sort)
+            .build()
+    })
+}
+
+/// Converts find/replace options to the equivalent find/update options, for `find_one_and_replace` replacements performed as pipelines
+pub(crate) fn find_and_update_options(options: Option<FindOneAndReplaceOptions>) -> Option<FindOneAndUpdateOptions> {
+    options.map(|o| {
+        FindOneAndUpdateOptions::builder()
+            .bypass_document_validation(o.bypass_document_validation)
+            .max_time(o.max_time)
+            .projection(o.projection)
+            .return_document(o.return_document)
+            .sort(o.sort)
+            .upsert(o.upsert)
+            .write_concern(o.write_concern)
+            .collation(o.collation)
+            .hint(o.hint)
+            .let_vars(o.let_vars)
+            .comment(o.comment)
+            .build()
+    })
+}
+
+/// Converts replace options to the equivalent update options, for replacements performed as pipelines
+pub(crate) fn update_options(options: Option<ReplaceOptions>) -> Option<UpdateOptions> {
+    options.map(|o| {
+        UpdateOptions::builder()
+            .bypass_document_validation(o.bypass_document_validation)
+            .upsert(o.upsert)
+            .collation(o.collation)
+            .hint(o.hint)
+            .write_concern(o.write_concern)
+            .let_vars(o.let_vars)
+            .comment(o.comment)
+            .sort(o.sort)
+            .build()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{DateTime, Document, doc};
+    use mongodb::options::UpdateModifications;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{Replacement, created_filter, stamp_replace, stamp_update, touches};
+    use crate::{
+        collection::Collection,
+        error::MResult,
+        hooks::Hooks,
+        model::Model,
+        testing::{NOW, Record},
+    };
+
+    /// A model without timestamps
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Plain {
+        #[serde(rename = "_id")]
+        id: i64,
+    }
+
+    impl Hooks for Plain {}
+
+    impl Model for Plain {
+        type Id = i64;
+
+        fn from_document(document: Document, _: Option<Collection<Self>>) -> MResult<Self> {
+            Ok(bson::from_document(document)?)
+        }
+
+        fn collection_name() -> String {
+            String::from("plain")
+        }
+
+        fn own_collection(&self) -> Option<Collection<Self>> {
+            None
+        }
+
+        fn id(&self) -> i64 {
+            self.id
+        }
+
+        fn generate_id() -> i64 {
+            0
+        }
+
+        fn attach_collection(&mut self, _: Collection<Self>) {}
+    }
+
+    fn stamped(update: Document) -> Document {
+        match stamp_update::<Record>(UpdateModifications::Document(update)).unwrap() {
+            UpdateModifications::Document(update) => update,
+            other => panic!("unexpected update {other:?}"),
+        }
+    }
+
+    fn replaced(record: &mut Record, keep_id: bool) -> Vec<Document> {
+        match stamp_replace(record, keep_id).unwrap() {
+            Replacement::Pipeline(pipeline) => pipeline,
+            Replacement::Document => panic!("expected a pipeline"),
+        }
+    }
+
+    #[test]
+    fn finds_touched_paths() {
+        let update = doc! {"$set": {"a": 1}, "$unset": {"b": ""}};
+        assert!(touches(&update, "a"));
+        assert!(touches(&update, "b"));
+        assert!(!touches(&update, "c"));
+        assert!(!touches(&doc! {"$set": {"a.c": 1}}, "a"));
+    }
+
+    #[test]
+    fn sets_timestamps() {
+        assert_eq!(
+            stamped(doc! {"$set": {"name": "a"}}),
+            doc! {"$set": {"name": "a", "updated": NOW}, "$setOnInsert": {"created": NOW}}
+        );
+        assert_eq!(
+            stamped(doc! {"$inc": {"count": 1}}),
+            doc! {"$inc": {"count": 1}, "$set": {"updated": NOW}, "$setOnInsert": {"created": NOW}}
+        );
+    }
+
+    #[test]
+    fn keeps_timestamps_set_by_the_update() {
+        let update = doc! {"$set": {"updated": DateTime::MIN}, "$setOnInsert": {"created": DateTime::MIN}};
+        assert_eq!(stamped(update.clone()), update);
+        let update = doc! {"$unset": {"updated": ""}};
+        assert_eq!(stamped(update), doc! {"$unset": {"updated": ""}, "$setOnInsert": {"created": NOW}});
+    }
+
+    #[test]
+    fn stamps_pipelines() {
+        match stamp_update::<Record>(UpdateModifications::Pipeline(vec![doc! {"$set": {"a": 1}}])).unwrap() {
+            UpdateModifications::Pipeline(pipeline) => assert_eq!(
+                pipeline,
+                vec![
+                    doc! {"$set": {"a": 1}},
+                    doc! {"$set": {"updated": {"$literal": NOW}, "created": {"$ifNull": ["$created", {"$literal": NOW}]}}}
+                ]
+            ),
+            other => panic!("unexpected update {other:?}"),
+        }
+    }
+
+    #[test]
+    fn keeps_stored_created_at_on_replace() {
+        let mut record = Record::new(1);
+        assert_eq!(
+            replaced(&mut record, false),
+            vec![doc! {"$replaceWith": {"$mergeObjects": [
+                {"$literal": {"_id": 1_i64, "created": null, "updated": NOW, "version": 0_i64}},
+                {"created": {"$ifNull": ["$created", {"$literal": NOW}]}}
+            ]}}]
+        );
+
+        record.created = Some(DateTime::MIN);
+        assert_eq!(
+            replaced(&mut record, false),
+            vec![doc! {"$replaceWith": {"$mergeObjects": [
+                {"$literal": {"_id": 1_i64, "created": DateTime::MIN, "updated": NOW, "version": 0_i64}},
+                {"created": {"$ifNull": ["$created", {"$literal": DateTime::MIN}]}}
+            ]}}]
+        );
+    }
+
+    #[test]
+    fn keeps_stored_id_on_replace() {
+        assert_eq!(
+            replaced(&mut Record::new(1), true),
+            vec![doc! {"$replaceWith": {"$mergeObjects": [
+                {"_id": {"$ifNull": ["$_id", {"$literal": 1_i64}]}},
+                {"$literal": {"created": null, "updated": NOW, "version": 0_i64}},
+                {"created": {"$ifNull": ["$created", {"$literal": NOW}]}}
+            ]}}]
+        );
+    }
+
+    #[test]
+    fn replaces_by_id_with_documents_without_created_at() {
+        // Only document replacements are reported as replacements by change streams
+        assert!(matches!(stamp_replace(&mut Plain { id: 1 }, false).unwrap(), Replacement::Document));
+        assert!(matches!(stamp_replace(&mut Plain { id: 1 }, true).unwrap(), Replacement::Pipeline(_)));
+        assert!(matches!(stamp_replace(&mut Record::new(1), false).unwrap(), Replacement::Pipeline(_)));
+    }
+
+    #[test]
+    fn guards_replacements_by_created_at() {
+        let mut record = Record::new(1);
+        assert_eq!(created_filter(&doc! {"_id": 1_i64}, &record).unwrap(), None);
+        record.created = Some(DateTime::MIN);
+        assert_eq!(
+            created_filter(&doc! {"_id": 1_i64}, &record).unwrap(),
+            Some(doc! {"_id": 1_i64, "created": DateTime::MIN})
+        );
+        assert_eq!(created_filter(&doc! {"_id": 1_i64}, &Plain { id: 1 }).unwrap(), None);
+    }
+}
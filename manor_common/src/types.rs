@@ -1,8 +1,11 @@
+use std::collections::{HashMap, HashSet};
+
 use serde::{Deserialize, Serialize};
 
 use crate::{
     MANOR_CLIENT,
     client::Client,
+    collection::id_key,
     error::{Error, MResult},
     model::Model,
 };
@@ -10,13 +13,16 @@ use crate::{
 /// A Link struct, representing a document in another collection.
 /// The referenced model must implement [Model]
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct Link<M: Model + Send + Sync> {
     /// Name of the collection. Not used directly, but useful for external parsing.
     pub collection: String,
 
     /// The ID of the targeted document
     pub id: M::Id,
-    #[serde(skip, default = "Option::<M>::default")]
+
+    /// The resolved document. Never serialized, but read from `_resolved` when populated by an aggregation (see [crate::populate::FindMany::populate]).
+    #[serde(skip_serializing, default = "Option::<M>::default", rename = "_resolved")]
     resolved: Option<M>,
 
     #[serde(skip, default)]
@@ -66,6 +72,45 @@ impl<M: Model + Send + Sync> Link<M> {
         }
     }
 
+    /// Resolves every unresolved link in `links` with a single `$in` query (see [crate::collection::Collection::get_many]), filling each link's cached value.
+    /// Returns the IDs of any linked documents that could not be found, each listed once, in the order they first appear in `links`.
+    ///
+    /// The query uses the client of the first unresolved link.
+    pub async fn resolve_all(links: &mut [Link<M>]) -> MResult<Vec<M::Id>> {
+        let Some(first) = links.iter().find(|link| link.resolved.is_none()) else {
+            return Ok(Vec::new());
+        };
+        let collection = first.client().collection::<M>();
+
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut pending: Vec<(String, M::Id)> = Vec::new();
+        for link in links.iter().filter(|link| link.resolved.is_none()) {
+            let key = id_key::<M>(&link.id)?;
+            if seen.insert(key.clone()) {
+                pending.push((key, link.id.clone()));
+            }
+        }
+
+        let found: HashMap<String, M> = collection
+            .get_many(pending.iter().map(|(_, id)| id.clone()))
+            .await?
+            .into_iter()
+            .map(|document| Ok((id_key::<M>(&document.id())?, document)))
+            .collect::<MResult<_>>()?;
+
+        for link in links.iter_mut().filter(|link| link.resolved.is_none()) {
+            if let Some(document) = found.get(&id_key::<M>(&link.id)?) {
+                link.resolved = Some(document.clone());
+            }
+        }
+
+        Ok(pending
+            .into_iter()
+            .filter(|(key, _)| !found.contains_key(key))
+            .map(|(_, id)| id)
+            .collect())
+    }
+
     /// Gets a reference to the contained value, if resolved
     pub fn value(&self) -> Option<&M> {
         self.resolved.as_ref()
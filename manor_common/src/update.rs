@@ -0,0 +1,204 @@
+use std::marker::PhantomData;
+
+use bson::{Bson, Document};
+use mongodb::options::UpdateModifications;
+use serde::Serialize;
+
+use crate::{
+    collection::serialize_value,
+    error::{Error, MResult},
+    model::Model,
+    query::Field,
+};
+
+/// A typed set of update operators against documents of a [Model], built from [Field] descriptors.
+///
+/// Values are serialized through the field's Rust type, and serialization errors are deferred until the update is used.
+///
+/// ```no_run
+/// # use manor::{schema, Collection, MResult, Update, bson::Uuid};
+/// # #[schema(collection = "users")]
+/// # pub struct User {
+/// #     #[field(id = Uuid::new)]
+/// #     pub id: Uuid,
+/// #     pub name: String,
+/// #     pub logins: i64,
+/// #     pub tags: Vec<String>,
+/// # }
+/// # async fn run(id: Uuid) -> MResult<()> {
+/// let update = Update::new()
+///     .set(User::fields.name, "alice")
+///     .inc(User::fields.logins, 1)
+///     .push(User::fields.tags, "admin");
+/// Collection::<User>::new().update_one(User::fields.id.eq(id), update).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Update<M> {
+    document: Result<Document, bson::ser::Error>,
+    _marker: PhantomData<fn() -> M>,
+}
+
+impl<M> Clone for Update<M> {
+    fn clone(&self) -> Self {
+        Self {
+            document: self.document.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<M> std::fmt::Debug for Update<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Update").field(&self.document).finish()
+    }
+}
+
+impl<M> Default for Update<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> Update<M> {
+    /// Creates an empty update
+    pub fn new() -> Self {
+        Self {
+            document: Ok(Document::new()),
+            _marker: PhantomData,
+        }
+    }
+
+    fn operator(mut self, operator: &str, path: &str, value: Result<Bson, bson::ser::Error>) -> Self {
+        match (&mut self.document, value) {
+            (Ok(document), Ok(value)) => {
+                if let Bson::Document(entries) = document
+                    .entry(operator.to_string())
+                    .or_insert_with(|| Bson::Document(Document::new()))
+                {
+                    entries.insert(path, value);
+                }
+            }
+            (Ok(_), Err(e)) => self.document = Err(e),
+            (Err(_), _) => (),
+        }
+        self
+    }
+
+    /// Sets a field to `value` (`$set`)
+    pub fn set<T: Serialize>(self, field: Field<M, T>, value: impl Into<T>) -> Self {
+        self.operator("$set", field.path(), serialize_value(&value.into()))
+    }
+
+    /// Removes a field from the document (`$unset`)
+    pub fn unset<T>(self, field: Field<M, T>) -> Self {
+        self.operator("$unset", field.path(), Ok(Bson::String(String::new())))
+    }
+
+    /// Increments a numeric field by `amount`, which may be negative (`$inc`)
+    pub fn inc<T: Serialize>(self, field: Field<M, T>, amount: impl Into<T>) -> Self {
+        self.operator("$inc", field.path(), serialize_value(&amount.into()))
+    }
+
+    /// Appends `value` to an array field (`$push`)
+    pub fn push<T: Serialize>(self, field: Field<M, Vec<T>>, value: impl Into<T>) -> Self {
+        self.operator("$push", field.path(), serialize_value(&value.into()))
+    }
+
+    /// Removes all instances of `value` from an array field (`$pull`)
+    pub fn pull<T: Serialize>(self, field: Field<M, Vec<T>>, value: impl Into<T>) -> Self {
+        self.operator("$pull", field.path(), serialize_value(&value.into()))
+    }
+
+    /// Appends `value` to an array field, unless it is already present (`$addToSet`)
+    pub fn add_to_set<T: Serialize>(self, field: Field<M, Vec<T>>, value: impl Into<T>) -> Self {
+        self.operator("$addToSet", field.path(), serialize_value(&value.into()))
+    }
+
+    /// Sets a field to the server's current date (`$currentDate`). Stored as a BSON date, so this should only be used on fields that deserialize from one (ie [bson::DateTime]).
+    pub fn current_date<T>(self, field: Field<M, T>) -> Self {
+        self.operator("$currentDate", field.path(), Ok(Bson::Boolean(true)))
+    }
+
+    /// Returns the compiled update document
+    pub fn document(&self) -> MResult<Document> {
+        self.document.clone().map_err(Error::from)
+    }
+}
+
+impl<M> TryFrom<Update<M>> for UpdateModifications {
+    type Error = Error;
+
+    fn try_from(value: Update<M>) -> Result<Self, Self::Error> {
+        value
+            .document
+            .map(UpdateModifications::Document)
+            .map_err(Error::from)
+    }
+}
+
+/// A trait for values that can be used as an update against a [Model]'s collection. Implemented for raw [Document]s, aggregation pipelines, [UpdateModifications] and typed [Update]s.
+pub trait IntoUpdate<M: Model + Send + Sync> {
+    /// Converts this value into [UpdateModifications]
+    fn into_update(self) -> MResult<UpdateModifications>;
+}
+
+impl<M: Model + Send + Sync> IntoUpdate<M> for Document {
+    fn into_update(self) -> MResult<UpdateModifications> {
+        Ok(UpdateModifications::Document(self))
+    }
+}
+
+impl<M: Model + Send + Sync> IntoUpdate<M> for Vec<Document> {
+    fn into_update(self) -> MResult<UpdateModifications> {
+        Ok(UpdateModifications::Pipeline(self))
+    }
+}
+
+impl<M: Model + Send + Sync> IntoUpdate<M> for UpdateModifications {
+    fn into_update(self) -> MResult<UpdateModifications> {
+        Ok(self)
+    }
+}
+
+impl<M: Model + Send + Sync> IntoUpdate<M> for Update<M> {
+    fn into_update(self) -> MResult<UpdateModifications> {
+        self.try_into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{Bson, doc};
+    use uuid::Uuid;
+
+    use super::Update;
+    use crate::query::Field;
+
+    #[test]
+    fn writes_uuids_as_binary() {
+        let id = Uuid::new_v4();
+        let update = Update::<()>::new()
+            .set(Field::<(), Uuid>::new("owner"), id)
+            .push(Field::<(), Vec<Uuid>>::new("members"), id)
+            .document()
+            .unwrap();
+        assert!(matches!(update.get_document("$set").unwrap().get("owner"), Some(Bson::Binary(_))));
+        assert!(matches!(update.get_document("$push").unwrap().get("members"), Some(Bson::Binary(_))));
+    }
+
+    #[test]
+    fn groups_operators() {
+        let update = Update::<()>::new()
+            .set(Field::<(), String>::new("name"), "a")
+            .set(Field::<(), i64>::new("age"), 3)
+            .unset(Field::<(), String>::new("nick"))
+            .inc(Field::<(), i64>::new("logins"), 1)
+            .document()
+            .unwrap();
+        assert_eq!(
+            update,
+            doc! {"$set": {"name": "a", "age": 3_i64}, "$unset": {"nick": ""}, "$inc": {"logins": 1_i64}}
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use std::{collections::HashMap, fmt::Display};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// A simple email address pattern, requiring a local part, an `@` and a dotted domain
+static EMAIL: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s.]+$").unwrap());
+
+/// A single failed validation rule, reported by [crate::error::Error::Validation]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    /// Serialized path of the invalid field
+    pub field: String,
+
+    /// Description of the failed rule
+    pub message: String,
+}
+
+impl ValidationError {
+    /// Creates a validation error for a field
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Values with a length, checked by `#[field(validate(length(...)))]`. Optional values are only checked if present.
+pub trait HasLength {
+    /// Returns the length of this value (in characters, for strings), or [None] to skip validation
+    fn length(&self) -> Option<usize>;
+}
+
+impl HasLength for str {
+    fn length(&self) -> Option<usize> {
+        Some(self.chars().count())
+    }
+}
+
+impl HasLength for String {
+    fn length(&self) -> Option<usize> {
+        self.as_str().length()
+    }
+}
+
+impl<T> HasLength for Vec<T> {
+    fn length(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<K, V, S> HasLength for HashMap<K, V, S> {
+    fn length(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<T: HasLength> HasLength for Option<T> {
+    fn length(&self) -> Option<usize> {
+        self.as_ref().and_then(T::length)
+    }
+}
+
+/// Values with a text representation, checked by `#[field(validate(regex = "...", email))]`. Optional values are only checked if present.
+pub trait HasText {
+    /// Returns the text of this value, or [None] to skip validation
+    fn text(&self) -> Option<&str>;
+}
+
+impl HasText for str {
+    fn text(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl HasText for String {
+    fn text(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl<T: HasText> HasText for Option<T> {
+    fn text(&self) -> Option<&str> {
+        self.as_ref().and_then(T::text)
+    }
+}
+
+/// Ordered values, checked by `#[field(validate(range(...)))]`. Optional values are only checked if present.
+pub trait HasRange {
+    /// The type of the bounds
+    type Value: PartialOrd + Display;
+
+    /// Returns the value to compare, or [None] to skip validation
+    fn value(&self) -> Option<&Self::Value>;
+}
+
+macro_rules! impl_has_range {
+    ($($ty:ty),*) => {
+        $(
+            impl HasRange for $ty {
+                type Value = $ty;
+
+                fn value(&self) -> Option<&Self::Value> {
+                    Some(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_has_range!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64);
+
+impl<T: HasRange> HasRange for Option<T> {
+    type Value = T::Value;
+
+    fn value(&self) -> Option<&Self::Value> {
+        self.as_ref().and_then(T::value)
+    }
+}
+
+/// Checks that a value's length is within the given bounds (inclusive)
+pub fn length<T: HasLength + ?Sized>(value: &T, min: Option<usize>, max: Option<usize>) -> Result<(), String> {
+    match value.length() {
+        Some(length) if min.is_some_and(|min| length < min) => {
+            Err(format!("length must be at least {}", min.unwrap()))
+        }
+        Some(length) if max.is_some_and(|max| length > max) => {
+            Err(format!("length must be at most {}", max.unwrap()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a value is within the given bounds (inclusive)
+pub fn range<T: HasRange + ?Sized>(value: &T, min: Option<T::Value>, max: Option<T::Value>) -> Result<(), String> {
+    match (value.value(), min, max) {
+        (Some(value), Some(min), _) if *value < min => Err(format!("must be at least {min}")),
+        (Some(value), _, Some(max)) if *value > max => Err(format!("must be at most {max}")),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a value matches a regular expression
+pub fn pattern<T: HasText + ?Sized>(value: &T, pattern: &Regex) -> Result<(), String> {
+    match value.text() {
+        Some(text) if !pattern.is_match(text) => Err(format!("must match the pattern {}", pattern.as_str())),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a value looks like an email address
+pub fn email<T: HasText + ?Sized>(value: &T) -> Result<(), String> {
+    match value.text() {
+        Some(text) if !EMAIL.is_match(text) => Err(String::from("must be a valid email address")),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::{email, length, pattern, range};
+
+    #[test]
+    fn checks_length() {
+        assert_eq!(length("abc", Some(1), Some(3)), Ok(()));
+        assert_eq!(length("", Some(1), None), Err(String::from("length must be at least 1")));
+        assert_eq!(length("abcd", None, Some(3)), Err(String::from("length must be at most 3")));
+        assert_eq!(length("äöü", None, Some(3)), Ok(()));
+        assert_eq!(length(&vec![1, 2], Some(3), None), Err(String::from("length must be at least 3")));
+        assert_eq!(length(&None::<String>, Some(1), None), Ok(()));
+        assert_eq!(length(&Some(String::new()), Some(1), None), Err(String::from("length must be at least 1")));
+    }
+
+    #[test]
+    fn checks_range() {
+        assert_eq!(range(&5, Some(0), Some(5)), Ok(()));
+        assert_eq!(range(&-1, Some(0), None), Err(String::from("must be at least 0")));
+        assert_eq!(range(&1.5, None, Some(1.0)), Err(String::from("must be at most 1")));
+        assert_eq!(range(&None::<u8>, Some(1), None), Ok(()));
+        assert_eq!(range(&Some(0_u8), Some(1), None), Err(String::from("must be at least 1")));
+    }
+
+    #[test]
+    fn checks_pattern() {
+        let slug = Regex::new("^[a-z0-9_]+$").unwrap();
+        assert_eq!(pattern("user_1", &slug), Ok(()));
+        assert_eq!(pattern("Not Valid", &slug), Err(String::from("must match the pattern ^[a-z0-9_]+$")));
+        assert_eq!(pattern(&None::<String>, &slug), Ok(()));
+    }
+
+    #[test]
+    fn checks_email() {
+        assert_eq!(email("alice@example.com"), Ok(()));
+        for invalid in ["alice", "alice@example", "@example.com", "alice@exa mple.com", "alice@example."] {
+            assert_eq!(email(invalid), Err(String::from("must be a valid email address")), "{invalid}");
+        }
+        assert_eq!(email(&None::<String>), Ok(()));
+    }
+}
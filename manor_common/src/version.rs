@@ -0,0 +1,157 @@
+use bson::{Bson, Document, doc};
+use mongodb::options::UpdateModifications;
+
+use crate::{
+    collection::{Collection, with_session},
+    error::{Error, MResult},
+    model::Model,
+    timestamps::touches,
+};
+
+/// Restricts a query to documents at the expected version. Documents stored without a version are treated as version `0`.
+pub(crate) fn version_filter<M: Model + Send + Sync>(mut query: Document, expected: i64) -> Document {
+    if let Some(field) = M::version_field() {
+        if expected == 0 {
+            query.insert(field, doc! {"$in": [0, Bson::Null]});
+        } else {
+            query.insert(field, expected);
+        }
+    }
+    query
+}
+
+/// Prepares a replacement of a `#[field(version)]` model: the query is restricted to the document's current version, which is then incremented.
+/// Returns the restricted query and the expected version (if versioned).
+pub(crate) fn bump<M: Model + Send + Sync>(query: Document, document: &mut M) -> (Document, Option<i64>) {
+    match document.version() {
+        Some(expected) => {
+            document.set_version(expected + 1);
+            (version_filter::<M>(query, expected), Some(expected))
+        }
+        None => (query, None),
+    }
+}
+
+/// Returns `true` if an update only sets the soft-delete field (ie a soft deletion or restore)
+fn marks_deleted<M: Model + Send + Sync>(update: &Document) -> bool {
+    let Some(field) = M::soft_delete_field() else {
+        return false;
+    };
+    update.len() == 1 && update.get_document("$set").is_ok_and(|fields| fields.len() == 1 && fields.contains_key(field))
+}
+
+/// Adds a version increment to an update, unless the update already sets the version field.
+/// Soft deletions and restores do not change the document's contents, so they are not versioned.
+pub(crate) fn bump_update<M: Model + Send + Sync>(update: UpdateModifications) -> UpdateModifications {
+    let Some(field) = M::version_field() else {
+        return update;
+    };
+
+    match update {
+        UpdateModifications::Document(mut update) => {
+            if !touches(&update, field) && !marks_deleted::<M>(&update) {
+                let increments = update
+                    .entry(String::from("$inc"))
+                    .or_insert_with(|| Bson::Document(Document::new()));
+                if let Bson::Document(increments) = increments {
+                    increments.insert(field, 1);
+                }
+            }
+            UpdateModifications::Document(update)
+        }
+        UpdateModifications::Pipeline(mut pipeline) => {
+            pipeline.push(doc! {"$set": {field: {"$add": [{"$ifNull": [format!("${field}"), 0]}, 1]}}});
+            UpdateModifications::Pipeline(pipeline)
+        }
+        other => other,
+    }
+}
+
+impl<M: Model + Send + Sync> Collection<M> {
+    /// Returns [Error::Conflict] if a document matching `query` is stored at a version other than `expected`
+    pub(crate) async fn conflict(&self, query: Document, expected: i64) -> MResult<Option<Error>> {
+        let Some(field) = M::version_field() else {
+            return Ok(None);
+        };
+
+        let collection = self.client.database().collection::<Document>(&self.name());
+        let action = collection.find_one(query).projection(doc! {"_id": 1, field: 1});
+        let Some(stored) = with_session!(self, action)? else {
+            return Ok(None);
+        };
+
+        let found = match stored.get(field) {
+            Some(Bson::Int32(version)) => Some(*version as i64),
+            Some(Bson::Int64(version)) => Some(*version),
+            _ => None,
+        };
+        if found.unwrap_or_default() == expected {
+            return Ok(None);
+        }
+
+        Ok(Some(Error::Conflict {
+            id: stored.get("_id").cloned().unwrap_or(Bson::Null).to_string(),
+            expected,
+            found,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{Document, doc};
+    use mongodb::options::UpdateModifications;
+
+    use super::bump_update;
+    use crate::testing::Record;
+
+    fn bumped(update: Document) -> Document {
+        match bump_update::<Record>(UpdateModifications::Document(update)) {
+            UpdateModifications::Document(update) => update,
+            other => panic!("unexpected update {other:?}"),
+        }
+    }
+
+    #[test]
+    fn increments_version() {
+        assert_eq!(
+            bumped(doc! {"$set": {"name": "a"}}),
+            doc! {"$set": {"name": "a"}, "$inc": {"version": 1}}
+        );
+        assert_eq!(
+            bumped(doc! {"$inc": {"count": 1}}),
+            doc! {"$inc": {"count": 1, "version": 1}}
+        );
+    }
+
+    #[test]
+    fn keeps_explicit_version() {
+        let update = doc! {"$set": {"version": 7}};
+        assert_eq!(bumped(update.clone()), update);
+        let update = doc! {"$inc": {"version": 2}};
+        assert_eq!(bumped(update.clone()), update);
+    }
+
+    #[test]
+    fn skips_soft_deletes() {
+        let deletion = doc! {"$set": {"deleted_at": bson::DateTime::now()}};
+        assert_eq!(bumped(deletion.clone()), deletion);
+        let restore = doc! {"$set": {"deleted_at": null}};
+        assert_eq!(bumped(restore.clone()), restore);
+        assert_eq!(
+            bumped(doc! {"$set": {"deleted_at": null, "name": "a"}}),
+            doc! {"$set": {"deleted_at": null, "name": "a"}, "$inc": {"version": 1}}
+        );
+    }
+
+    #[test]
+    fn bumps_pipelines() {
+        match bump_update::<Record>(UpdateModifications::Pipeline(vec![])) {
+            UpdateModifications::Pipeline(pipeline) => assert_eq!(
+                pipeline,
+                vec![doc! {"$set": {"version": {"$add": [{"$ifNull": ["$version", 0]}, 1]}}}]
+            ),
+            other => panic!("unexpected update {other:?}"),
+        }
+    }
+}
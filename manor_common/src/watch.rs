@@ -0,0 +1,218 @@
+use std::{sync::Arc, task::Poll};
+
+use bson::{Document, from_bson};
+use futures_core::Stream;
+use futures_util::{
+    StreamExt,
+    stream::{self, BoxStream},
+};
+use mongodb::change_stream::event::{ChangeStreamEvent, OperationType};
+
+pub use mongodb::change_stream::event::ResumeToken;
+
+use crate::{
+    collection::Collection,
+    error::{Error, MResult},
+    model::Model,
+};
+
+/// The fields changed by an update operation
+#[derive(Clone, Debug)]
+pub struct Changes {
+    /// Updated fields (as dotted paths) and their new values
+    pub updated: Document,
+
+    /// Paths of removed fields
+    pub removed: Vec<String>,
+}
+
+/// A typed change event from a [ChangeStream]
+#[derive(Clone, Debug)]
+pub enum ChangeEvent<M: Model + Send + Sync> {
+    /// A new document was inserted
+    Inserted(M),
+
+    /// An existing document was updated
+    Updated {
+        /// ID of the updated document
+        id: M::Id,
+
+        /// The fields changed by the update
+        changes: Changes,
+    },
+
+    /// An existing document was replaced.
+    ///
+    /// Replacements that must keep stored fields run as update pipelines, which the server reports as updates. Replacing by a query other than the document's ID
+    /// yields [ChangeEvent::Updated] instead, as does replacing a document of a model with a `#[field(created_at)]` field by ID (ie through [Model::save])
+    /// when its `created_at` is unset or differs from the stored value.
+    Replaced(M),
+
+    /// A document was deleted
+    Deleted(M::Id),
+
+    /// Any other event affecting the collection (ie drop, rename or invalidate)
+    Other(OperationType),
+}
+
+/// A persistence hook for change stream resume tokens, allowing a [ChangeStream] to pick up where it left off after a restart.
+#[async_trait::async_trait]
+pub trait ResumeTokenStore: Send + Sync {
+    /// Loads the last stored token, if any
+    async fn load(&self) -> MResult<Option<ResumeToken>>;
+
+    /// Stores the token of the last fully processed event
+    async fn store(&self, token: ResumeToken) -> MResult<()>;
+}
+
+/// A typed wrapper around [mongodb::change_stream::ChangeStream] that yields [ChangeEvent]s. Returned documents are loaded like those of a find (attaching the watched collection,
+/// and running [crate::hooks::Hooks::after_load]).
+pub struct ChangeStream<M: Model + Send + Sync> {
+    pub(crate) base: BoxStream<'static, MResult<(ResumeToken, ChangeEvent<M>)>>,
+    pub(crate) resume_token: Option<ResumeToken>,
+}
+
+impl<M: Model + Send + Sync> ChangeStream<M> {
+    /// Returns the resume token of the last event yielded by this stream (or the token it was started from), which can be passed to [Collection::watch_from]
+    pub fn resume_token(&self) -> Option<ResumeToken> {
+        self.resume_token.clone()
+    }
+}
+
+impl<M: Model + Send + Sync> Stream for ChangeStream<M> {
+    type Item = MResult<ChangeEvent<M>>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        match self.base.as_mut().poll_next(cx) {
+            Poll::Ready(Some(Ok((token, event)))) => {
+                self.resume_token = Some(token);
+                Poll::Ready(Some(Ok(event)))
+            }
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<M: Model + Send + Sync> Collection<M> {
+    /// Converts a raw change event, preparing any full document like a find would (see [Collection::load])
+    async fn change_event(&self, event: ChangeStreamEvent<Document>) -> MResult<ChangeEvent<M>> {
+        let id = || {
+            event
+                .document_key
+                .as_ref()
+                .and_then(|key| key.get("_id"))
+                .and_then(|id| from_bson::<M::Id>(id.clone()).ok())
+                .ok_or(Error::NotFound)
+        };
+
+        let document = || async {
+            let document = event.full_document.clone().ok_or(Error::NotFound)?;
+            self.load(M::from_document(document, None)?).await
+        };
+
+        Ok(match event.operation_type {
+            OperationType::Insert => ChangeEvent::Inserted(document().await?),
+            OperationType::Replace => ChangeEvent::Replaced(document().await?),
+            OperationType::Update => ChangeEvent::Updated {
+                id: id()?,
+                changes: event
+                    .update_description
+                    .as_ref()
+                    .map(|d| Changes {
+                        updated: d.updated_fields.clone(),
+                        removed: d.removed_fields.clone(),
+                    })
+                    .unwrap_or(Changes {
+                        updated: Document::new(),
+                        removed: Vec::new(),
+                    }),
+            },
+            OperationType::Delete => ChangeEvent::Deleted(id()?),
+            ref other => ChangeEvent::Other(other.clone()),
+        })
+    }
+
+    async fn open_change_stream(
+        &self,
+        filter: Document,
+        resume_after: Option<ResumeToken>,
+        store: Option<Arc<dyn ResumeTokenStore>>,
+    ) -> MResult<ChangeStream<M>> {
+        let pipeline = if filter.is_empty() {
+            Vec::new()
+        } else {
+            vec![bson::doc! {"$match": filter}]
+        };
+        let inner = self
+            .collection()
+            .clone_with_type::<Document>()
+            .watch()
+            .pipeline(pipeline)
+            .resume_after(resume_after.clone())
+            .await?;
+
+        // Each event's token is only stored once the next event is requested, so an event is not marked as processed until the consumer is done with it
+        let collection = self.clone();
+        let base = stream::unfold(
+            (inner, None::<ResumeToken>),
+            move |(mut inner, pending)| {
+                let collection = collection.clone();
+                let store = store.clone();
+                async move {
+                    if let (Some(store), Some(token)) = (store, pending)
+                        && let Err(e) = store.store(token).await
+                    {
+                        return Some((Err(e), (inner, None)));
+                    }
+
+                    let result = match inner.next().await? {
+                        Ok(event) => {
+                            let token = event.id.clone();
+                            collection.change_event(event).await.map(|e| (token, e))
+                        }
+                        Err(e) => Err(Error::from(e)),
+                    };
+                    let pending = result.as_ref().ok().map(|(token, _)| token.clone());
+                    Some((result, (inner, pending)))
+                }
+            },
+        );
+
+        Ok(ChangeStream {
+            base: Box::pin(base),
+            resume_token: resume_after,
+        })
+    }
+
+    /// Watches this collection for changes, returning a [ChangeStream] of typed events.
+    ///
+    /// `filter` is applied as a `$match` stage over the raw change events (ie `doc! {"operationType": "insert"}`), and may be empty.
+    pub async fn watch(&self, filter: impl Into<Document>) -> MResult<ChangeStream<M>> {
+        self.open_change_stream(filter.into(), None, None).await
+    }
+
+    /// Watches this collection for changes, resuming after a previously obtained [ResumeToken] (see [ChangeStream::resume_token])
+    pub async fn watch_from(
+        &self,
+        filter: impl Into<Document>,
+        token: ResumeToken,
+    ) -> MResult<ChangeStream<M>> {
+        self.open_change_stream(filter.into(), Some(token), None).await
+    }
+
+    /// Watches this collection for changes, resuming from the token in `store` (if any) and persisting each event's token to it once the event has been processed
+    pub async fn watch_with_store(
+        &self,
+        filter: impl Into<Document>,
+        store: impl ResumeTokenStore + 'static,
+    ) -> MResult<ChangeStream<M>> {
+        let token = store.load().await?;
+        self.open_change_stream(filter.into(), token, Some(Arc::new(store)))
+            .await
+    }
+}
@@ -0,0 +1,38 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::DeriveInput;
+
+use crate::util::{SerdeField, is_option, schema_of};
+
+pub(crate) fn derive_bson_schema(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    let syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(fields), .. }) = &input.data else {
+        return TokenStream::from(
+            darling::Error::unsupported_shape("BsonSchema can only be derived for structs with named fields.")
+                .write_errors(),
+        );
+    };
+
+    let properties = fields.named.iter().filter_map(|field| {
+        let serde = SerdeField::parse(&field.attrs);
+        if serde.skip {
+            return None;
+        }
+
+        let name = serde.rename.unwrap_or(field.ident.as_ref().unwrap().to_string());
+        let schema = schema_of(&field.ty);
+        let required = !serde.optional && !is_option(&field.ty);
+        Some(quote! {(#name, #schema, #required)})
+    });
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics manor::json_schema::BsonSchema for #name #type_generics #where_clause {
+            fn bson_schema() -> manor::bson::Document {
+                manor::json_schema::object_schema([#(#properties),*])
+            }
+        }
+    }
+    .into()
+}
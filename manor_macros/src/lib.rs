@@ -1,12 +1,13 @@
 use proc_macro::TokenStream;
 
+mod bson_schema;
 mod schema;
 mod util;
 
 /// This macro generates a filled-out Model from a template struct, as well as an associated Builder.
 /// The attribute itself follows this syntax:
 /// 
-/// ```
+/// ```text
 /// #[schema(collection = "optional collection name", schema_name = OptionalSchemaName, builder_name = OptionalBuilderName)]
 /// ```
 /// 
@@ -18,24 +19,109 @@ mod util;
 /// 
 /// Non-ID fields can be marked with `#[field(alias = "some string")]`. This is a simplified equivalent of `#[serde(rename = "value")]`.
 /// 
+/// ### Schema options
+/// 
+/// - `index(keys = "author, -created", unique, name = "...")` declares a compound index (keys are struct field names, prefixed with `-` when descending), applied by `Collection::sync_indexes()`.
+/// - `projection(Name: field, ...)` generates a partial struct implementing `manor::Projection`, which is loaded by `Collection::find_many_as` & `find_one_as`.
+/// - `hooks` leaves the `manor::Hooks` implementation to the schema, instead of generating an empty one.
+/// - `soft_delete` adds a `deleted_at` field. Deletions then mark documents instead of removing them (without applying delete policies), and reads skip marked documents
+///   (see `Collection::with_deleted`, `restore` & `purge`).
+/// - `track_changes` records a snapshot of loaded documents, so that `Model::save_changes` only writes the changed fields.
+/// 
+/// `#[backlink(Model::field)]`, placed below `#[schema(...)]`, generates a method returning every document linking to this one through that field (ie `sessions()`, unless `name = "..."` is given).
+/// 
+/// ### Field options
+/// 
+/// - `index`, `unique` and `ttl = "30d"` declare single-field indexes.
+/// - `validate(...)` declares validation rules (`length(min, max)`, `range(min, max)`, `regex = "..."`, `email` and `custom = "path"`), checked before inserts & replacements and by the builder's `build()`.
+///   `custom = "path"` names a function taking a reference to the field, `fn(&FieldType) -> Result<(), impl Into<String>>`,
+///   whose error becomes the message of the field's `ValidationError` (ie `fn not_reserved(name: &String) -> Result<(), String>`).
+/// - `created_at` & `updated_at` mark timestamp fields (a `bson::DateTime` or `chrono::DateTime<Utc>`, optionally in an `Option`), which are maintained on every write.
+///   Replacements keep the stored `created_at`: saving a document whose `created_at` matches the stored one is a plain replacement, while other replacements
+///   run as update pipelines, which change streams report as updates (see `ChangeEvent::Replaced`).
+/// - `version` marks an integer field used for optimistic concurrency, so that replacing a stale document fails with `Error::Conflict`.
+/// - `on_delete = "cascade"`, `"set_null"` or `"restrict"` declares what happens to this document when its linked document is deleted. Policies are applied within a transaction,
+///   so deleting the linked model requires a replica set or sharded cluster: on standalone servers, its deletions (including `Model::delete`) fail with `Error::Unsupported`
+///   unless the client opts into non-atomic deletes with `Client::allow_non_atomic_deletes`. Bulk writes reject deletions of the linked model.
+/// - `delete_file` deletes the linked GridFS files (in `FileLink` fields) once the owning document's deletion is committed (see `Session::delete_files`).
+/// 
+/// The macro also generates typed `manor::Field` descriptors as `<Schema>::fields`, used to build `manor::Query` filters & `manor::Update`s,
+/// and implements `manor::BsonSchema` for server-side validators (see `Collection::apply_validator`).
+/// 
 /// ---
 /// 
 /// An example schema:
-/// ```
-/// use manor::schema;
-/// use uuid::Uuid;
+/// ```no_run
+/// use manor::{schema, FileLink, Link};
+/// use manor::uuid::Uuid;
 /// 
-/// #[schema(collection = "users")]
+/// #[schema(collection = "users", index(keys = "name, -created"), projection(UserSummary: user_id, name))]
+/// #[backlink(Session::user)]
 /// pub struct User {
 ///     #[field(id = Uuid::new_v4)]
 ///     pub user_id: Uuid,
 ///     
-///     #[field(alias = "username")]
+///     #[field(alias = "username", unique, validate(length(min = 1, max = 32)))]
 ///     pub name: String,
-///     pub password: String
+///     pub password: String,
+/// 
+///     #[field(validate(custom = "not_reserved"))]
+///     pub nickname: Option<String>,
+/// 
+///     #[field(validate(range(min = 0, max = 5)))]
+///     pub rating: Option<f64>,
+/// 
+///     #[field(created_at)]
+///     pub created: manor::bson::DateTime,
+/// 
+///     #[field(version)]
+///     pub revision: u32,
+/// 
+///     #[serde(default)]
+///     #[field(delete_file)]
+///     pub avatar: Option<FileLink>,
+/// }
+/// 
+/// fn not_reserved(nickname: &Option<String>) -> Result<(), String> {
+///     match nickname.as_deref() {
+///         Some("admin" | "root") => Err("nickname is reserved".to_string()),
+///         _ => Ok(()),
+///     }
+/// }
+/// 
+/// #[schema(collection = "sessions", soft_delete)]
+/// pub struct Session {
+///     #[field(id = Uuid::new_v4)]
+///     pub id: Uuid,
+/// 
+///     #[field(index, on_delete = "cascade")]
+///     pub user: Link<User>,
+/// 
+///     #[field(ttl = "30d")]
+///     pub last_seen: manor::bson::DateTime,
 /// }
 /// ```
 #[proc_macro_attribute]
 pub fn schema(args: TokenStream, input: TokenStream) -> TokenStream {
     schema::generate_schema(args, input)
+}
+
+/// Derives `manor::BsonSchema` for a struct with named fields, describing it as a `$jsonSchema` object (respecting `#[serde(rename)]`, `#[serde(default)]` and `#[serde(skip)]`).
+/// Used to describe structs nested within `#[schema(...)]` models.
+/// 
+/// ```
+/// use manor::BsonSchema;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, BsonSchema)]
+/// pub struct Address {
+///     pub street: String,
+///     pub postcode: Option<String>,
+/// }
+///
+/// assert_eq!(Address::bson_schema().get_array("required").unwrap().len(), 1);
+/// ```
+#[proc_macro_derive(BsonSchema)]
+pub fn derive_bson_schema(input: TokenStream) -> TokenStream {
+    bson_schema::derive_bson_schema(input)
 }
\ No newline at end of file
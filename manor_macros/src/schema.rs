@@ -6,20 +6,246 @@ use syn::{
     parse::{Parse, Parser}, punctuated::Punctuated, token::Comma, Attribute, Expr, Field, Ident, ItemStruct, TypePath
 };
 
-use crate::util::catch;
+use crate::util::{SerdeField, catch, is_option, parse_duration, schema_of};
+
+#[derive(Debug, FromMeta, Default)]
+#[darling(default)]
+struct LengthArgs {
+    min: Option<usize>,
+    max: Option<usize>,
+}
+
+#[derive(Debug, FromMeta, Default)]
+#[darling(default)]
+struct RangeArgs {
+    min: Option<Expr>,
+    max: Option<Expr>,
+}
+
+#[derive(Debug, FromMeta, Default)]
+#[darling(default)]
+struct ValidateArgs {
+    length: Option<LengthArgs>,
+    range: Option<RangeArgs>,
+    regex: Option<String>,
+    email: bool,
+    custom: Option<syn::Path>,
+}
 
 #[derive(Debug, FromMeta, Default)]
 #[darling(default)]
 struct FieldArgs {
     id: Option<Expr>,
     alias: Option<String>,
+    index: bool,
+    unique: bool,
+    ttl: Option<String>,
+    on_delete: Option<String>,
+    validate: Option<ValidateArgs>,
+    created_at: bool,
+    updated_at: bool,
+    version: bool,
+    delete_file: bool,
+}
+
+#[derive(Debug, FromMeta)]
+struct IndexArgs {
+    keys: String,
+    #[darling(default)]
+    unique: bool,
+    #[darling(default)]
+    name: Option<String>,
+}
+
+/// A `projection(Name: field, ...)` declaration
+#[derive(Debug)]
+struct ProjectionArgs {
+    name: Ident,
+    fields: Vec<Ident>,
 }
+
+impl FromMeta for ProjectionArgs {
+    fn from_meta(item: &syn::Meta) -> darling::Result<Self> {
+        let syn::Meta::List(list) = item else {
+            return Err(darling::Error::custom("Expected projection(Name: field, ...)").with_span(item));
+        };
+        list.parse_args_with(|input: syn::parse::ParseStream| {
+            let name = input.parse::<Ident>()?;
+            input.parse::<syn::Token![:]>()?;
+            let fields = Punctuated::<Ident, Comma>::parse_terminated(input)?;
+            Ok(Self { name, fields: fields.into_iter().collect() })
+        })
+        .map_err(darling::Error::from)
+    }
+}
+
 #[derive(Debug, FromMeta, Default)]
 #[darling(default)]
 struct SchemaArgs {
     collection: Option<String>,
     schema_name: Option<IdentString>,
     builder_name: Option<IdentString>,
+    #[darling(multiple, rename = "index")]
+    indexes: Vec<IndexArgs>,
+    soft_delete: bool,
+    hooks: bool,
+    track_changes: bool,
+    #[darling(multiple, rename = "projection")]
+    projections: Vec<ProjectionArgs>,
+}
+
+/// How a field holds a `Link<T>`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LinkKind {
+    Single,
+    Optional,
+    Many,
+}
+
+/// Returns the generic argument of a type whose last path segment is `name` (ie `T` for `Option<T>`)
+fn generic_argument<'a>(ty: &'a syn::Type, name: &str) -> Option<&'a syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last().filter(|s| s.ident == name)?;
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    args.args.iter().find_map(|arg| match arg {
+        syn::GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    })
+}
+
+/// Extracts the linked model from a `Link<T>`, `Option<Link<T>>` or `Vec<Link<T>>` field type
+fn link_target(ty: &syn::Type) -> Option<(syn::Type, LinkKind)> {
+    if let Some(target) = generic_argument(ty, "Link") {
+        return Some((target.clone(), LinkKind::Single));
+    }
+    for (wrapper, kind) in [("Option", LinkKind::Optional), ("Vec", LinkKind::Many)] {
+        if let Some(target) = generic_argument(ty, wrapper).and_then(|inner| generic_argument(inner, "Link")) {
+            return Some((target.clone(), kind));
+        }
+    }
+    None
+}
+
+/// Returns `true` for `FileLink`, `Option<FileLink>` or `Vec<FileLink>` field types
+fn is_file_link(ty: &syn::Type) -> bool {
+    let is_link = |ty: &syn::Type| matches!(ty, syn::Type::Path(path) if path.path.segments.last().is_some_and(|s| s.ident == "FileLink"));
+    is_link(ty) || ["Option", "Vec"].iter().any(|wrapper| generic_argument(ty, wrapper).is_some_and(is_link))
+}
+
+/// Returns `f32` or `f64` for float field types (optionally in an `Option`)
+fn float_type(ty: &syn::Type) -> Option<Ident> {
+    let ty = generic_argument(ty, "Option").unwrap_or(ty);
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    path.path.get_ident().filter(|i| *i == "f32" || *i == "f64").cloned()
+}
+
+/// Generates the checks for a field's `validate(...)` rules, pushing failures into `errors`
+fn validation_checks(ident: &Ident, ty: &syn::Type, path: &str, rules: &ValidateArgs) -> darling::Result<Vec<proc_macro2::TokenStream>> {
+    let optional = |value: Option<proc_macro2::TokenStream>| value.map(|v| quote! {Some(#v)}).unwrap_or(quote! {None});
+    let mut checks: Vec<proc_macro2::TokenStream> = Vec::new();
+
+    if let Some(LengthArgs { min, max }) = rules.length.as_ref() {
+        let (min, max) = (optional(min.map(|v| quote! {#v})), optional(max.map(|v| quote! {#v})));
+        checks.push(quote! {manor::validate::length(&self.#ident, #min, #max)});
+    }
+    if let Some(RangeArgs { min, max }) = rules.range.as_ref() {
+        // Bounds of float fields are cast, so that integer literals (ie `min = 0`) compare against them
+        let bound = |v: &Expr| match float_type(ty) {
+            Some(float) => quote! {(#v) as #float},
+            None => quote! {#v},
+        };
+        let (min, max) = (optional(min.as_ref().map(bound)), optional(max.as_ref().map(bound)));
+        checks.push(quote! {manor::validate::range(&self.#ident, #min, #max)});
+    }
+    if let Some(pattern) = rules.regex.as_ref() {
+        if let Err(e) = regex::Regex::new(pattern) {
+            return Err(darling::Error::custom(format!("Invalid validation regex: {e}")));
+        }
+        checks.push(quote! {{
+            static PATTERN: manor::once_cell::sync::Lazy<manor::regex::Regex> = manor::once_cell::sync::Lazy::new(|| manor::regex::Regex::new(#pattern).unwrap());
+            manor::validate::pattern(&self.#ident, &PATTERN)
+        }});
+    }
+    if rules.email {
+        checks.push(quote! {manor::validate::email(&self.#ident)});
+    }
+    if let Some(custom) = rules.custom.as_ref() {
+        checks.push(quote! {#custom(&self.#ident)});
+    }
+
+    Ok(checks
+        .into_iter()
+        .map(|check| quote! {
+            if let Err(message) = #check {
+                errors.push(manor::ValidationError::new(#path, message));
+            }
+        })
+        .collect())
+}
+
+/// A reverse link declared on a schema with `#[backlink(Model::field)]` or `#[backlink(Model::field, name = "method")]`
+struct Backlink {
+    model: syn::Path,
+    field: Ident,
+    name: Ident,
+}
+
+impl Backlink {
+    fn parse(attr: &Attribute) -> syn::Result<Self> {
+        let items = attr.parse_args_with(Punctuated::<syn::Meta, Comma>::parse_terminated)?;
+        let mut items = items.into_iter();
+        let Some(syn::Meta::Path(mut model)) = items.next() else {
+            return Err(syn::Error::new_spanned(attr, "Expected a link field path, such as #[backlink(Session::user)]"));
+        };
+        let Some(field) = model.segments.pop().map(|s| s.into_value().ident) else {
+            return Err(syn::Error::new_spanned(attr, "Expected a link field path, such as #[backlink(Session::user)]"));
+        };
+        model.segments.pop_punct();
+        if model.segments.is_empty() {
+            return Err(syn::Error::new_spanned(attr, "Expected a link field path, such as #[backlink(Session::user)]"));
+        }
+
+        let model_name = model.segments.last().unwrap().ident.to_string();
+        let mut name = Ident::new(&format!("{}s", model_name.to_case(Case::Snake)), field.span());
+        for item in items {
+            match item {
+                syn::Meta::NameValue(syn::MetaNameValue { path, value: Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(value), .. }), .. }) if path.is_ident("name") => {
+                    name = value.parse()?;
+                }
+                other => return Err(syn::Error::new_spanned(other, "Unknown backlink option, expected `name = \"...\"`")),
+            }
+        }
+
+        Ok(Self { model, field, name })
+    }
+}
+
+/// Generates an [mongodb::IndexModel] expression from (key, direction) pairs
+fn index_model(keys: &[(String, i32)], name: Option<String>, unique: bool, ttl: Option<u64>) -> proc_macro2::TokenStream {
+    let name = name.unwrap_or_else(|| keys.iter().map(|(k, d)| format!("{k}_{d}")).collect::<Vec<String>>().join("_"));
+    let key_names = keys.iter().map(|(k, _)| k);
+    let directions = keys.iter().map(|(_, d)| d);
+    let unique = if unique { quote! {Some(true)} } else { quote! {None} };
+    let ttl = if let Some(seconds) = ttl { quote! {Some(std::time::Duration::from_secs(#seconds))} } else { quote! {None} };
+
+    quote! {
+        manor::mongodb::IndexModel::builder()
+            .keys(manor::bson::doc! {#(#key_names: #directions),*})
+            .options(
+                manor::mongodb::options::IndexOptions::builder()
+                    .name(#name.to_string())
+                    .unique(#unique)
+                    .expire_after(#ttl)
+                    .build()
+            )
+            .build()
+    }
 }
 
 pub(crate) fn generate_schema(_args: TokenStream, _input: TokenStream) -> TokenStream {
@@ -34,7 +260,15 @@ pub(crate) fn generate_schema(_args: TokenStream, _input: TokenStream) -> TokenS
                 .write_errors(),
         );
     };
-    let input_attrs = input.attrs;
+    let mut input_attrs: Vec<Attribute> = Vec::new();
+    let mut backlinks: Vec<Backlink> = Vec::new();
+    for attr in input.attrs {
+        if attr.path().is_ident("backlink") {
+            backlinks.push(catch!(Backlink::parse(&attr)));
+        } else {
+            input_attrs.push(attr);
+        }
+    }
 
     let args = catch!(SchemaArgs::from_list(&attr_args));
 
@@ -43,7 +277,7 @@ pub(crate) fn generate_schema(_args: TokenStream, _input: TokenStream) -> TokenS
     let formatted_gen_id_call = format!("{}::gen_id()", schema_name.as_str());
     let builder_name = args
         .builder_name
-        .and_then(|n| Some(n.as_str().to_string()))
+        .map(|n| n.as_str().to_string())
         .unwrap_or(format!("{}Builder", schema_name.as_str()));
     let collection_name = args
         .collection
@@ -54,8 +288,26 @@ pub(crate) fn generate_schema(_args: TokenStream, _input: TokenStream) -> TokenS
     let mut id_type: syn::Type = syn::Type::Path(catch!(TypePath::from_string("manor::bson::oid::ObjectId")));
     let mut id_generator: syn::Expr = syn::Expr::Path(catch!(syn::ExprPath::parse.parse(quote! {manor::bson::oid::ObjectId::new}.into())));
     let mut id_name: Option<Ident> = None;
+    let mut schema_fields: Vec<(Ident, syn::Type, String)> = Vec::new();
+    let mut indexes: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut link_rules: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut validations: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut skipped_fields: Vec<Ident> = Vec::new();
+    let mut optional_fields: Vec<Ident> = Vec::new();
+    let mut created_at: Option<(Ident, syn::Type, String)> = None;
+    let mut updated_at: Option<(Ident, syn::Type, String)> = None;
+    let mut version: Option<(Ident, String)> = None;
+    let mut file_fields: Vec<String> = Vec::new();
     for field in fields.named {
+        let serde = SerdeField::parse(&field.attrs);
+        if serde.skip {
+            skipped_fields.push(field.ident.clone().unwrap());
+        } else if serde.optional {
+            optional_fields.push(field.ident.clone().unwrap());
+        }
+
         let mut already_parsed = false;
+        let field_name = field.ident.clone().unwrap().to_string();
         for attr in field.attrs.clone() {
             if attr.path().is_ident("field") {
                 let parsed_field = catch!(FieldArgs::from_meta(&attr.meta));
@@ -84,6 +336,7 @@ pub(crate) fn generate_schema(_args: TokenStream, _input: TokenStream) -> TokenS
                     let id_field_attrs = unparsed_attrs.iter().filter(|i| !i.path().is_ident("field")).collect::<Vec<&Attribute>>();
                     
                     let id_ident = id_name.clone().unwrap();
+                    schema_fields.push((id_ident.clone(), id_type.clone(), String::from("_id")));
 
                     new_fields.push(catch!(
                         Field::parse_named.parse(
@@ -104,6 +357,86 @@ pub(crate) fn generate_schema(_args: TokenStream, _input: TokenStream) -> TokenS
                         attributes.extend(catch!(Attribute::parse_outer.parse(quote! {#[serde(rename = #alias)]}.into())));
                     }
 
+                    let serialized = parsed_field.alias.clone().or(serde.rename.clone()).unwrap_or(field_name.clone());
+                    schema_fields.push((field.ident.clone().unwrap(), field.ty.clone(), serialized.clone()));
+
+                    if parsed_field.index || parsed_field.unique || parsed_field.ttl.is_some() {
+                        let ttl = match parsed_field.ttl.as_ref().map(|t| parse_duration(t)) {
+                            Some(Some(seconds)) => Some(seconds),
+                            Some(None) => {
+                                return TokenStream::from(darling::Error::custom("Invalid TTL, expected a duration such as \"30d\", \"12h\", \"15m\" or \"3600s\".").with_span(&attr).write_errors());
+                            },
+                            None => None
+                        };
+                        indexes.push(index_model(&[(serialized.clone(), 1)], None, parsed_field.unique, ttl));
+                    }
+
+                    for (enabled, slot, marker) in [(parsed_field.created_at, &mut created_at, "created_at"), (parsed_field.updated_at, &mut updated_at, "updated_at")] {
+                        if !enabled {
+                            continue;
+                        }
+                        if slot.is_some() {
+                            return TokenStream::from(darling::Error::custom(format!("Only one field may be marked as {marker}")).with_span(&attr).write_errors());
+                        }
+                        *slot = Some((field.ident.clone().unwrap(), field.ty.clone(), serialized.clone()));
+                        let ty = &field.ty;
+                        let default = quote! {<#ty as manor::timestamps::Timestamp>::now()}.to_string();
+                        attributes.extend(catch!(Attribute::parse_outer.parse(quote! {#[builder(default = #default)]}.into())));
+                    }
+
+                    if parsed_field.version {
+                        if version.is_some() {
+                            return TokenStream::from(darling::Error::custom("Only one field may be marked as version").with_span(&attr).write_errors());
+                        }
+                        version = Some((field.ident.clone().unwrap(), serialized.clone()));
+                        attributes.extend(catch!(Attribute::parse_outer.parse(quote! {#[serde(default)] #[builder(default)]}.into())));
+                    }
+
+                    if parsed_field.delete_file {
+                        if !is_file_link(&field.ty) {
+                            return TokenStream::from(darling::Error::custom("delete_file requires a FileLink, Option<FileLink> or Vec<FileLink> field").with_span(&attr).write_errors());
+                        }
+                        file_fields.push(serialized.clone());
+                    }
+
+                    if let Some(rules) = parsed_field.validate.as_ref() {
+                        let checks = match validation_checks(field.ident.as_ref().unwrap(), &field.ty, &serialized, rules) {
+                            Ok(checks) => checks,
+                            Err(e) => return TokenStream::from(e.with_span(&attr).write_errors()),
+                        };
+                        validations.extend(checks);
+                    }
+
+                    if let Some(on_delete) = parsed_field.on_delete.as_ref() {
+                        let Some((target, kind)) = link_target(&field.ty) else {
+                            return TokenStream::from(darling::Error::custom("on_delete requires a Link<T>, Option<Link<T>> or Vec<Link<T>> field").with_span(&attr).write_errors());
+                        };
+                        let action = match on_delete.as_str() {
+                            "cascade" => quote! {manor::integrity::OnDelete::Cascade},
+                            "set_null" if kind != LinkKind::Single => quote! {manor::integrity::OnDelete::SetNull},
+                            "set_null" => {
+                                return TokenStream::from(darling::Error::custom("on_delete = \"set_null\" requires an Option<Link<T>> or Vec<Link<T>> field").with_span(&attr).write_errors());
+                            },
+                            "restrict" => quote! {manor::integrity::OnDelete::Restrict},
+                            _ => {
+                                return TokenStream::from(darling::Error::custom("Invalid on_delete policy, expected \"cascade\", \"set_null\" or \"restrict\"").with_span(&attr).write_errors());
+                            }
+                        };
+                        let many = kind == LinkKind::Many;
+                        link_rules.push(quote! {
+                            manor::inventory::submit! {
+                                manor::integrity::LinkRule {
+                                    target: || <#target as manor::Model>::collection_name(),
+                                    source: || <#schema_name as manor::Model>::collection_name(),
+                                    path: #serialized,
+                                    many: #many,
+                                    action: #action,
+                                    cascade: manor::integrity::cascade::<#schema_name>,
+                                }
+                            }
+                        });
+                    }
+
                     let mut new_field = field.clone();
                     new_field.attrs.extend(attributes);
                     new_field.attrs = new_field.attrs.iter().filter_map(|f| if f.path().is_ident("field") {None} else {Some(f.clone())}).collect();
@@ -115,10 +448,121 @@ pub(crate) fn generate_schema(_args: TokenStream, _input: TokenStream) -> TokenS
         }
 
         if !already_parsed {
+            schema_fields.push((field.ident.clone().unwrap(), field.ty.clone(), serde.rename.unwrap_or(field_name)));
             new_fields.push(field.clone());
         }
     }
 
+    if id_name.is_none() {
+        schema_fields.insert(0, (catch!(Ident::from_string("id")), id_type.clone(), String::from("_id")));
+    }
+
+    if args.soft_delete {
+        if schema_fields.iter().any(|(_, _, path)| path == "deleted_at") {
+            return TokenStream::from(darling::Error::custom("soft_delete adds a deleted_at field, which is already defined").write_errors());
+        }
+        let deleted_ident = catch!(Ident::from_string("deleted_at"));
+        let deleted_type: syn::Type = syn::Type::Path(catch!(TypePath::from_string("Option<manor::bson::DateTime>")));
+        schema_fields.push((deleted_ident.clone(), deleted_type.clone(), String::from("deleted_at")));
+        new_fields.push(catch!(
+            syn::Field::parse_named.parse(
+                quote! {
+                    #[serde(default)]
+                    #[builder(setter(skip))]
+                    pub #deleted_ident: #deleted_type
+                }
+                .into()
+            )
+        ));
+    }
+    let mut projections: Vec<proc_macro2::TokenStream> = Vec::new();
+    for projection in args.projections {
+        let mut projected: Vec<&(Ident, syn::Type, String)> = Vec::new();
+        for name in projection.fields.iter() {
+            match schema_fields.iter().find(|(ident, _, _)| ident == name) {
+                Some(field) if !skipped_fields.contains(&field.0) => projected.push(field),
+                _ => {
+                    return TokenStream::from(darling::Error::custom(format!("Unknown field in projection {}: {name}", projection.name)).with_span(name).write_errors());
+                }
+            }
+        }
+
+        if projected.is_empty() {
+            return TokenStream::from(darling::Error::custom("Projection declarations require at least one field").with_span(&projection.name).write_errors());
+        }
+        let projection_name = &projection.name;
+        let projection_doc = format!(
+            "Projection of [{}] to the fields `{}`. Generated by `#[schema(projection(...))]`.",
+            schema_name.as_str(),
+            projection.fields.iter().map(|f| f.to_string()).collect::<Vec<String>>().join(", ")
+        );
+        let projected_fields = projected.iter().map(|(ident, ty, path)| {
+            // The field's own serde attributes (ie `with` or `skip_serializing_if`) are kept, so it (de)serializes like the schema's field
+            let serde_attrs = new_fields
+                .iter()
+                .find(|f| f.ident.as_ref() == Some(ident))
+                .map(|f| f.attrs.iter().filter(|a| a.path().is_ident("serde")).cloned().collect::<Vec<Attribute>>())
+                .unwrap_or_default();
+            let serde = SerdeField::parse(&serde_attrs);
+            let rename = if serde.rename.is_none() && *ident != path {
+                quote! {#[serde(rename = #path)]}
+            } else {
+                quote! {}
+            };
+            let default = if is_option(ty) && !serde.default {
+                quote! {#[serde(default)]}
+            } else {
+                quote! {}
+            };
+            quote! {
+                #(#serde_attrs)*
+                #rename
+                #default
+                pub #ident: #ty
+            }
+        });
+        let projected_paths = projected.iter().map(|(_, _, path)| path);
+        projections.push(quote! {
+            #[doc = #projection_doc]
+            #[derive(Clone, Debug, manor::serde::Serialize, manor::serde::Deserialize)]
+            pub struct #projection_name {
+                #(#projected_fields),*
+            }
+
+            impl manor::projection::Projection<#schema_name> for #projection_name {
+                fn fields() -> Vec<&'static str> {
+                    vec![#(#projected_paths),*]
+                }
+            }
+        });
+    }
+
+    let soft_delete_field = if args.soft_delete {
+        quote! {Some("deleted_at")}
+    } else {
+        quote! {None}
+    };
+
+    for compound in args.indexes {
+        let mut keys: Vec<(String, i32)> = Vec::new();
+        for key in compound.keys.split(',').map(|k| k.trim()).filter(|k| !k.is_empty()) {
+            let (direction, path) = match key.strip_prefix('-') {
+                Some(stripped) => (-1, stripped),
+                None => (1, key.trim_start_matches('+')),
+            };
+            let (root, rest) = path.split_once('.').map(|(r, p)| (r, Some(p))).unwrap_or((path, None));
+            let Some((_, _, serialized)) = schema_fields.iter().find(|(name, _, _)| *name == root) else {
+                return TokenStream::from(darling::Error::custom(format!("Unknown field in index keys: {root}")).write_errors());
+            };
+            keys.push((rest.map(|r| format!("{serialized}.{r}")).unwrap_or(serialized.clone()), direction));
+        }
+
+        if keys.is_empty() {
+            return TokenStream::from(darling::Error::custom("Index declarations require at least one key").write_errors());
+        }
+        indexes.push(index_model(&keys, compound.name, compound.unique, None));
+    }
+
     if id_name.is_none() {
         new_fields.insert(
             0,
@@ -146,12 +590,156 @@ pub(crate) fn generate_schema(_args: TokenStream, _input: TokenStream) -> TokenS
         )
     ));
 
+    let snapshot_impl = if args.track_changes {
+        new_fields.push(catch!(
+            syn::Field::parse_named.parse(
+                quote! {
+                    #[serde(skip)]
+                    #[builder(setter(skip), default = "None")]
+                    _snapshot: Option<manor::bson::Document>
+                }
+                .into()
+            )
+        ));
+        quote! {
+            fn tracks_changes() -> bool {
+                true
+            }
+            fn snapshot(&self) -> Option<&manor::bson::Document> {
+                self._snapshot.as_ref()
+            }
+            fn set_snapshot(&mut self, snapshot: Option<manor::bson::Document>) {
+                self._snapshot = snapshot;
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     let assembled_fields = new_fields.into_token_stream();
+    let fields_name = Ident::new(&format!("{}Fields", schema_name.as_str()), schema_name.span());
+    let fields_doc = format!("Typed field descriptors for [{}], accessed through `{}::fields`", schema_name.as_str(), schema_name.as_str());
+    let descriptor_idents = schema_fields.iter().map(|(ident, _, _)| ident).collect::<Vec<&Ident>>();
+    let descriptor_types = schema_fields.iter().map(|(_, ty, _)| ty);
+    let descriptor_paths = schema_fields.iter().map(|(_, _, path)| path);
+    let descriptor_docs = schema_fields.iter().map(|(ident, _, path)| format!("Descriptor for `{ident}` (serialized as `{path}`)"));
+    let validate_body = if validations.is_empty() {
+        quote! {Ok(())}
+    } else {
+        quote! {
+            let mut errors: Vec<manor::ValidationError> = Vec::new();
+            #(#validations)*
+            if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(manor::Error::Validation(errors))
+            }
+        }
+    };
+    let timestamp_path = |field: &Option<(Ident, syn::Type, String)>| match field {
+        Some((_, _, path)) => quote! {Some(#path)},
+        None => quote! {None},
+    };
+    let (created_path, updated_path) = (timestamp_path(&created_at), timestamp_path(&updated_at));
+    let timestamp_values = created_at.iter().chain(updated_at.iter()).map(|(_, ty, path)| {
+        quote! {timestamps.insert(#path, manor::bson::to_bson(&<#ty as manor::timestamps::Timestamp>::now())?);}
+    });
+    let touch_created = created_at.iter().map(|(ident, ty, _)| quote! {
+        if created && !manor::timestamps::Timestamp::is_set(&self.#ident) {
+            self.#ident = <#ty as manor::timestamps::Timestamp>::now();
+        }
+    });
+    let touch_updated = updated_at.iter().map(|(ident, ty, _)| quote! {self.#ident = <#ty as manor::timestamps::Timestamp>::now();});
+    let timestamp_methods = if created_at.is_some() || updated_at.is_some() {
+        quote! {
+            fn timestamp_fields() -> (Option<&'static str>, Option<&'static str>) {
+                (#created_path, #updated_path)
+            }
+            fn timestamps() -> manor::MResult<manor::bson::Document> {
+                let mut timestamps = manor::bson::Document::new();
+                #(#timestamp_values)*
+                Ok(timestamps)
+            }
+            fn touch(&mut self, created: bool) {
+                #(#touch_created)*
+                #(#touch_updated)*
+                let _ = created;
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let version_methods = match &version {
+        Some((ident, path)) => quote! {
+            fn version_field() -> Option<&'static str> {
+                Some(#path)
+            }
+            #[allow(clippy::unnecessary_cast)]
+            fn version(&self) -> Option<i64> {
+                Some(self.#ident as i64)
+            }
+            fn set_version(&mut self, version: i64) {
+                self.#ident = version as _;
+            }
+        },
+        None => quote! {},
+    };
+    let hooks_impl = if args.hooks {
+        quote! {}
+    } else {
+        quote! {impl manor::Hooks for #schema_name {}}
+    };
+    let builder_ident = Ident::new(&builder_name, schema_name.span());
+    let builder_error_ident = Ident::new(&format!("{builder_name}Error"), schema_name.span());
+    let schema_name_str = schema_name.to_string();
     let id_alias = id_name.unwrap_or(catch!(Ident::from_string("id")));
+    let schema_properties = schema_fields.iter().filter(|(ident, _, _)| !skipped_fields.contains(ident)).map(|(ident, ty, path)| {
+        let schema = schema_of(ty);
+        let required = path == "_id" || (!is_option(ty) && !optional_fields.contains(ident));
+        quote! {(#path, #schema, #required)}
+    });
+    let link_fields = schema_fields.iter().filter_map(|(_, ty, path)| {
+        let (target, kind) = link_target(ty)?;
+        let kind = match kind {
+            LinkKind::Single => quote! {manor::integrity::LinkKind::Single},
+            LinkKind::Optional => quote! {manor::integrity::LinkKind::Optional},
+            LinkKind::Many => quote! {manor::integrity::LinkKind::Many},
+        };
+        Some(quote! {
+            manor::integrity::LinkField {
+                path: #path,
+                target: || <#target as manor::Model>::collection_name(),
+                target_deleted: <#target as manor::Model>::soft_delete_field,
+                kind: #kind,
+            }
+        })
+    });
+    let backlink_methods = backlinks.iter().map(|Backlink { model, field, name }| {
+        let doc = format!("Finds every document whose `{field}` link points at this document. Generated by `#[backlink(...)]`.");
+        quote! {
+            #[doc = #doc]
+            pub async fn #name(&self) -> manor::MResult<manor::Cursor<#model>> {
+                <Self as manor::Model>::collection(self)
+                    .sibling::<#model>()
+                    .find_many(#model::fields.#field.id().eq(<Self as manor::Model>::id(self)))
+                    .await
+            }
+        }
+    });
+    let backlink_indexes = backlinks.iter().map(|Backlink { model, field, .. }| {
+        quote! {
+            manor::inventory::submit! {
+                manor::index::ForeignIndex {
+                    collection: || <#model as manor::Model>::collection_name(),
+                    index: || manor::index::field_index(#model::fields.#field.id().path()),
+                }
+            }
+        }
+    });
 
     quote! {
         #[derive(Clone, Debug, manor::serde::Serialize, manor::serde::Deserialize, manor::derive_builder::Builder)]
-        #[builder(name = #builder_name, crate = "manor::derive_builder", setter(into, strip_option))]
+        #[builder(name = #builder_name, crate = "manor::derive_builder", setter(into, strip_option), build_fn(private, name = "build_unchecked"))]
         #(#input_attrs)*
         pub struct #schema_name {
             #assembled_fields
@@ -161,6 +749,58 @@ pub(crate) fn generate_schema(_args: TokenStream, _input: TokenStream) -> TokenS
             fn gen_id() -> #id_type {
                 #id_generator()
             }
+
+            /// Typed field descriptors for this model, used to build [manor::Query] filters
+            #[allow(non_upper_case_globals)]
+            pub const fields: #fields_name = #fields_name {
+                #(#descriptor_idents: manor::Field::new(#descriptor_paths)),*
+            };
+
+            #(#backlink_methods)*
+        }
+
+        #(#backlink_indexes)*
+
+        #(#link_rules)*
+
+        #hooks_impl
+
+        #(#projections)*
+
+        impl #builder_ident {
+            /// Builds a new model, then checks its validation rules (see [manor::Model::validate])
+            pub fn build(&self) -> Result<#schema_name, #builder_error_ident> {
+                let built = self.build_unchecked()?;
+                manor::Model::validate(&built).map_err(|e| #builder_error_ident::ValidationError(e.to_string()))?;
+                Ok(built)
+            }
+
+            /// Builds a new model like `build()`, returning a [manor::Error] that lists every failed validation rule
+            pub fn build_validated(&self) -> manor::MResult<#schema_name> {
+                let built = self.build_unchecked().map_err(|e| match e {
+                    #builder_error_ident::UninitializedField(field) => manor::Error::UninitializedField(field.to_string()),
+                    #builder_error_ident::ValidationError(message) => {
+                        manor::Error::Validation(vec![manor::ValidationError::new(#schema_name_str, message)])
+                    }
+                })?;
+                manor::Model::validate(&built)?;
+                Ok(built)
+            }
+        }
+
+        impl manor::json_schema::BsonSchema for #schema_name {
+            fn bson_schema() -> manor::bson::Document {
+                manor::json_schema::object_schema([#(#schema_properties),*])
+            }
+        }
+
+        #[doc = #fields_doc]
+        #[derive(Clone, Debug)]
+        pub struct #fields_name {
+            #(
+                #[doc = #descriptor_docs]
+                pub #descriptor_idents: manor::Field<#schema_name, #descriptor_types>
+            ),*
         }
 
         impl manor::Model for #schema_name {
@@ -186,6 +826,27 @@ pub(crate) fn generate_schema(_args: TokenStream, _input: TokenStream) -> TokenS
             fn attach_collection(&mut self, collection: manor::Collection<Self>) -> () {
                 self._collection = Some(collection.clone());
             }
+            #snapshot_impl
+            fn indexes() -> Vec<manor::mongodb::IndexModel> {
+                vec![#(#indexes),*]
+            }
+            fn validate(&self) -> manor::MResult<()> {
+                #validate_body
+            }
+            #timestamp_methods
+            #version_methods
+            fn soft_delete_field() -> Option<&'static str> {
+                #soft_delete_field
+            }
+            fn json_schema() -> manor::bson::Document {
+                <Self as manor::json_schema::BsonSchema>::bson_schema()
+            }
+            fn file_fields() -> Vec<&'static str> {
+                vec![#(#file_fields),*]
+            }
+            fn link_fields() -> Vec<manor::integrity::LinkField> {
+                vec![#(#link_fields),*]
+            }
         }
     }
     .into()
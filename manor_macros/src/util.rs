@@ -10,3 +10,85 @@ macro_rules! catch {
 }
 
 pub(crate) use catch;
+
+/// Parses a duration such as `"30d"`, `"12h"`, `"90m"`, `"45s"`, `"2w"` or `"3600"` into seconds.
+pub(crate) fn parse_duration(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(
+        value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len()),
+    );
+    let multiplier = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => return None,
+    };
+
+    number.parse::<u64>().ok().map(|n| n * multiplier)
+}
+
+/// The `#[serde(...)]` options of a field that affect its serialized form
+#[derive(Debug, Default)]
+pub(crate) struct SerdeField {
+    pub rename: Option<String>,
+    pub default: bool,
+    pub optional: bool,
+    pub skip: bool,
+}
+
+impl SerdeField {
+    /// Collects the relevant options from a field's `#[serde(...)]` attributes, ignoring anything else
+    pub(crate) fn parse(attrs: &[syn::Attribute]) -> Self {
+        let mut info = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+            let _ = attr.parse_nested_meta(|meta| {
+                let value = if meta.input.peek(syn::Token![=]) {
+                    Some(meta.value()?.parse::<syn::Expr>()?)
+                } else {
+                    if meta.input.peek(syn::token::Paren) {
+                        meta.parse_nested_meta(|nested| {
+                            if nested.input.peek(syn::Token![=]) {
+                                nested.value()?.parse::<syn::Expr>()?;
+                            }
+                            Ok(())
+                        })?;
+                    }
+                    None
+                };
+
+                if meta.path.is_ident("rename") {
+                    if let Some(syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(name), .. })) = value {
+                        info.rename = Some(name.value());
+                    }
+                } else if meta.path.is_ident("default") {
+                    info.default = true;
+                    info.optional = true;
+                } else if meta.path.is_ident("skip_serializing_if") {
+                    info.optional = true;
+                } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_serializing") {
+                    info.skip = true;
+                }
+                Ok(())
+            });
+        }
+        info
+    }
+}
+
+/// Returns `true` if a type's last path segment is `Option`
+pub(crate) fn is_option(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Path(path) if path.path.segments.last().is_some_and(|s| s.ident == "Option"))
+}
+
+/// Generates an expression evaluating to the `$jsonSchema` of a field type, falling back to an unconstrained schema if it does not implement `BsonSchema`
+pub(crate) fn schema_of(ty: &syn::Type) -> proc_macro2::TokenStream {
+    quote::quote! {{
+        #[allow(unused_imports)]
+        use manor::json_schema::{SchemaFallback as _, SchemaKnown as _};
+        (&&manor::json_schema::SchemaOf::<#ty>::new()).schema()
+    }}
+}